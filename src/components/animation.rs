@@ -0,0 +1,130 @@
+use super::{MeshComponent, TransformComponent};
+use crate::resources::animation::AnimationClip;
+use slotmap::DefaultKey;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationState {
+    pub clip: usize,
+    pub time: f32,
+    // false until the first update, which also passes the start time itself
+    pub started: bool,
+}
+
+impl AnimationState {
+    fn new(clip: usize) -> Self {
+        Self {
+            clip,
+            time: 0.0,
+            started: false,
+        }
+    }
+}
+
+// Local transform and morph target weights of an animation target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub translation: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub scale: glam::Vec3,
+    pub weights: Vec<f32>,
+}
+
+impl Pose {
+    pub fn new(transform: &TransformComponent, mesh: Option<&MeshComponent>) -> Self {
+        Self {
+            translation: transform.translation,
+            rotation: transform.rotation,
+            scale: transform.scale,
+            weights: mesh.map_or_else(Vec::new, |mesh| mesh.weights.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationEvent {
+    pub clip: usize,
+    pub time: f32,
+    pub name: String,
+}
+
+// Channels of the clips refer to their entities by index into `targets`, so the clips
+// can be shared between all instances of a prefab.
+#[derive(Debug, Clone)]
+pub struct AnimationComponent {
+    pub clips: Vec<Rc<AnimationClip>>,
+    pub targets: Vec<DefaultKey>,
+    // poses of the targets before any clip moved them, one per target, for the
+    // properties the playing clips don't animate
+    pub bind_pose: Vec<Pose>,
+    pub current: Option<AnimationState>,
+    // clip that is faded out while blending to `current`
+    pub previous: Option<AnimationState>,
+    pub blend_time: f32,
+    pub blend_duration: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub events: Vec<AnimationEvent>,
+    // names of the events passed during the last update
+    pub fired_events: Vec<String>,
+}
+
+impl Default for AnimationComponent {
+    fn default() -> Self {
+        Self {
+            clips: Vec::new(),
+            targets: Vec::new(),
+            bind_pose: Vec::new(),
+            current: None,
+            previous: None,
+            blend_time: 0.0,
+            blend_duration: 0.0,
+            speed: 1.0,
+            looping: true,
+            events: Vec::new(),
+            fired_events: Vec::new(),
+        }
+    }
+}
+
+// Playback controls for game code, not all of them are used by the demo.
+#[allow(dead_code)]
+impl AnimationComponent {
+    pub fn play(&mut self, clip: usize) {
+        self.current = Some(AnimationState::new(clip));
+        self.previous = None;
+    }
+
+    // Fades from the playing clip to `clip` over `duration` seconds.
+    pub fn blend_to(&mut self, clip: usize, duration: f32) {
+        if self.current.is_none() || duration <= 0.0 {
+            return self.play(clip);
+        }
+
+        self.previous = self.current;
+        self.current = Some(AnimationState::new(clip));
+        self.blend_time = 0.0;
+        self.blend_duration = duration;
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.previous = None;
+    }
+
+    pub fn find_clip(&self, name: &str) -> Option<usize> {
+        self.clips
+            .iter()
+            .position(|clip| clip.name.as_deref() == Some(name))
+    }
+
+    // Fires `name` whenever playback of `clip` passes the given keyframe of one of its
+    // channels.
+    pub fn add_keyframe_event(&mut self, clip: usize, channel: usize, keyframe: usize, name: &str) {
+        self.events.push(AnimationEvent {
+            clip,
+            time: self.clips[clip].channels[channel].times[keyframe],
+            name: name.to_string(),
+        });
+    }
+}
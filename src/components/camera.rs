@@ -1,5 +1,16 @@
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    // `ymag` is half the height of the view volume, its width follows the aspect ratio.
+    Orthographic {
+        ymag: f32,
+    },
+}
+
 #[derive(Default, Debug, Clone, Copy)]
 pub struct CameraComponent {
+    pub projection: Projection,
     pub fov: f32,
     pub aspect: f32,
     pub near: f32,
@@ -9,16 +20,48 @@ pub struct CameraComponent {
 
 impl CameraComponent {
     pub fn new(fov: f32, aspect: f32, near: f32, far: f32) -> Self {
-        Self {
+        let mut camera = Self {
+            projection: Projection::Perspective,
             fov,
             aspect,
             near,
             far,
-            proj: glam::Mat4::perspective_lh(fov, aspect, near, far),
-        }
+            proj: glam::Mat4::identity(),
+        };
+
+        camera.update_projection_matrix();
+
+        camera
+    }
+
+    pub fn orthographic(ymag: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let mut camera = Self {
+            projection: Projection::Orthographic { ymag },
+            fov: 0.0,
+            aspect,
+            near,
+            far,
+            proj: glam::Mat4::identity(),
+        };
+
+        camera.update_projection_matrix();
+
+        camera
     }
 
     pub fn update_projection_matrix(&mut self) {
-        self.proj = glam::Mat4::perspective_lh(self.fov, self.aspect, self.near, self.far);
+        self.proj = match self.projection {
+            Projection::Perspective if self.far.is_infinite() => {
+                glam::Mat4::perspective_infinite_lh(self.fov, self.aspect, self.near)
+            }
+            Projection::Perspective => {
+                glam::Mat4::perspective_lh(self.fov, self.aspect, self.near, self.far)
+            }
+            Projection::Orthographic { ymag } => {
+                let xmag = ymag * self.aspect;
+
+                glam::Mat4::orthographic_lh(-xmag, xmag, -ymag, ymag, self.near, self.far)
+            }
+        };
     }
 }
@@ -0,0 +1,5 @@
+// Application specific data attached to an entity, e.g. the `extras` of a glTF node.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExtrasComponent {
+    pub extras: serde_json::Value,
+}
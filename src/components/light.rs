@@ -0,0 +1,32 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+// Directional and spot lights shine along the local +Z axis of their entity.
+#[derive(Debug, Clone, Copy)]
+pub struct LightComponent {
+    pub kind: LightKind,
+    pub color: glam::Vec3,
+    pub intensity: f32,
+    pub range: Option<f32>,
+    // only directional and spot lights have shadows
+    pub cast_shadows: bool,
+}
+
+impl Default for LightComponent {
+    fn default() -> Self {
+        Self {
+            kind: LightKind::Point,
+            color: glam::Vec3::one(),
+            intensity: 1.0,
+            range: None,
+            cast_shadows: true,
+        }
+    }
+}
@@ -1,5 +1,70 @@
-#[derive(Debug, Default, Clone, Copy)]
+use crate::gpu::uniform::MATERIAL_TEXTURE_COUNT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureInfo {
+    pub texture_id: usize,
+    pub tex_coord: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct PbrMaterial {
     pub color: glam::Vec4,
-    pub color_texture: Option<usize>,
+    pub color_texture: Option<TextureInfo>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub metallic_roughness_texture: Option<TextureInfo>,
+    pub normal_texture: Option<TextureInfo>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureInfo>,
+    pub occlusion_strength: f32,
+    pub emissive: glam::Vec3,
+    pub emissive_texture: Option<TextureInfo>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            color: glam::Vec4::one(),
+            color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: glam::Vec3::zero(),
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
+
+impl PbrMaterial {
+    // In the order of the texture bindings of the lit shader.
+    pub fn texture_slots(&self) -> [Option<TextureInfo>; MATERIAL_TEXTURE_COUNT] {
+        [
+            self.color_texture,
+            self.metallic_roughness_texture,
+            self.normal_texture,
+            self.occlusion_texture,
+            self.emissive_texture,
+        ]
+    }
+
+    pub fn textures(&self) -> impl Iterator<Item = TextureInfo> {
+        IntoIterator::into_iter(self.texture_slots()).flatten()
+    }
 }
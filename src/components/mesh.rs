@@ -1,9 +1,26 @@
 use super::MeshPrimitive;
 use smallvec::SmallVec;
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct MeshComponent {
     pub primitives: SmallVec<[MeshPrimitive; 4]>,
+    // morph target weights, shared by all primitives
+    pub weights: Vec<f32>,
+    // whether the mesh is drawn into shadow maps
+    pub cast_shadows: bool,
+    // whether shadows of other meshes fall onto the mesh
+    pub receive_shadows: bool,
+}
+
+impl Default for MeshComponent {
+    fn default() -> Self {
+        Self {
+            primitives: SmallVec::new(),
+            weights: Vec::new(),
+            cast_shadows: true,
+            receive_shadows: true,
+        }
+    }
 }
 
 impl MeshComponent {
@@ -1,12 +1,22 @@
+mod animation;
 mod camera;
+mod extras;
+mod light;
 pub mod material;
 mod mesh;
+mod name;
 mod player;
 mod primitive;
+mod skin;
 mod transform;
 
-pub use camera::CameraComponent;
+pub use animation::{AnimationComponent, AnimationState, Pose};
+pub use camera::{CameraComponent, Projection};
+pub use extras::ExtrasComponent;
+pub use light::{LightComponent, LightKind};
 pub use mesh::MeshComponent;
+pub use name::NameComponent;
 pub use player::PlayerComponent;
 pub use primitive::MeshPrimitive;
+pub use skin::SkinComponent;
 pub use transform::TransformComponent;
@@ -0,0 +1,12 @@
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NameComponent {
+    pub name: String,
+}
+
+impl NameComponent {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
@@ -5,4 +5,6 @@ pub struct MeshPrimitive {
     pub geometry_id: usize,
     pub pipeline_id: usize,
     pub material: PbrMaterial,
+    // bind group with the textures of `material`
+    pub material_bind_group_id: usize,
 }
@@ -0,0 +1,8 @@
+use slotmap::DefaultKey;
+
+// Joint `i` deforms the vertices weighted to it by `joints[i].world * inverse_bind_matrices[i]`.
+#[derive(Debug, Default, Clone)]
+pub struct SkinComponent {
+    pub joints: Vec<DefaultKey>,
+    pub inverse_bind_matrices: Vec<glam::Mat4>,
+}
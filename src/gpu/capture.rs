@@ -0,0 +1,120 @@
+use std::{io, path::Path};
+
+// RGBA8 pixels of a frame read back from a headless context, rows from top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Capture {
+    // Buffers a texture is copied into have their rows padded to
+    // `wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`.
+    pub fn from_padded_rows(width: u32, height: u32, bytes_per_row: u32, data: &[u8]) -> Self {
+        let row_len = width as usize * 4;
+
+        let pixels = data
+            .chunks(bytes_per_row as usize)
+            .take(height as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect();
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // Binary PPM, which drops the alpha channel.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut out_data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+
+        out_data.reserve(self.pixels.len() / 4 * 3);
+
+        for pixel in self.pixels.chunks(4) {
+            out_data.extend_from_slice(&pixel[..3]);
+        }
+
+        out_data
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, image::ImageError> {
+        let mut out_data = Vec::new();
+
+        image::png::PngEncoder::new(&mut out_data).encode(
+            &self.pixels,
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )?;
+
+        Ok(out_data)
+    }
+
+    // Writes a .png or .ppm file, depending on the extension of `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+
+        let data = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("png") => self.to_png().map_err(io::Error::other)?,
+            Some("ppm") => self.to_ppm(),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}: captures are saved as .png or .ppm", path.display()),
+                ))
+            }
+        };
+
+        std::fs::write(path, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker() -> Capture {
+        Capture {
+            width: 2,
+            height: 2,
+            pixels: vec![
+                255, 0, 0, 255, 0, 255, 0, 255, //
+                0, 0, 255, 255, 255, 255, 255, 128,
+            ],
+        }
+    }
+
+    #[test]
+    fn from_padded_rows_drops_the_padding() {
+        let mut data = vec![0; 2 * 256];
+        data[..8].copy_from_slice(&checker().pixels[..8]);
+        data[256..264].copy_from_slice(&checker().pixels[8..]);
+
+        assert_eq!(Capture::from_padded_rows(2, 2, 256, &data), checker());
+    }
+
+    #[test]
+    fn to_ppm_writes_rgb() {
+        let ppm = checker().to_ppm();
+        let header = b"P6\n2 2\n255\n";
+
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(
+            &ppm[header.len()..],
+            &[255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn to_png_round_trips() {
+        let png = checker().to_png().unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgba8();
+
+        assert_eq!(image.dimensions(), (2, 2));
+        assert_eq!(image.into_raw(), checker().pixels);
+    }
+}
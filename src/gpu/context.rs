@@ -1,21 +1,109 @@
 use super::{
+    capture::Capture,
     uniform::{UniformContext, UniformLayouts},
     DEPTH_FORMAT,
 };
-use wgpu::{Adapter, Device, Instance, Queue, Surface, SwapChain, SwapChainDescriptor};
+use crate::resources::{manager::ResourceManager, scene::Scene};
+use slotmap::DefaultKey;
+use std::rc::Rc;
+use wgpu::{Adapter, Device, Queue, Surface, SwapChain, SwapChainDescriptor};
 use winit::dpi::PhysicalSize;
 
+// Format of the texture headless contexts render into.
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// Where frames end up.
+pub enum RenderTarget {
+    Window {
+        surface: Surface,
+        swap_chain_desc: SwapChainDescriptor,
+        swap_chain: SwapChain,
+    },
+    // texture which can be read back with `Context::capture`
+    Offscreen {
+        texture: wgpu::Texture,
+        view: wgpu::TextureView,
+    },
+}
+
+#[derive(Debug)]
+pub enum ContextError {
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    // only headless contexts can be captured
+    NotOffscreen,
+    MapBuffer(wgpu::BufferAsyncError),
+}
+
+impl std::fmt::Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ContextError::NoAdapter => write!(f, "no graphics adapter found"),
+            ContextError::RequestDevice(source) => {
+                write!(f, "failed to create device: {}", source)
+            }
+            ContextError::NotOffscreen => {
+                write!(f, "only frames of headless contexts can be captured")
+            }
+            ContextError::MapBuffer(source) => {
+                write!(f, "failed to read back the frame: {}", source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ContextError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ContextError::RequestDevice(source) => Some(source),
+            ContextError::MapBuffer(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
 pub struct Context {
     size: PhysicalSize<u32>,
-    pub instance: Instance,
-    pub surface: Surface,
-    pub adapter: Adapter,
     pub device: Device,
     pub queue: Queue,
-    pub swap_chain_desc: SwapChainDescriptor,
-    pub swap_chain: SwapChain,
+    // color format of the render target
+    pub format: wgpu::TextureFormat,
+    pub target: RenderTarget,
     pub uniforms: UniformContext,
-    pub uniform_layouts: UniformLayouts,
+    pub uniform_layouts: Rc<UniformLayouts>,
+}
+
+async fn request_device(adapter: &Adapter) -> Result<(Device, Queue), wgpu::RequestDeviceError> {
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+}
+
+fn create_offscreen_target(device: &Device, size: PhysicalSize<u32>) -> RenderTarget {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: size.width,
+            height: size.height,
+            depth: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: OFFSCREEN_FORMAT,
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        label: None,
+    });
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    RenderTarget::Offscreen { texture, view }
 }
 
 impl Context {
@@ -31,15 +119,7 @@ impl Context {
             .await
             .expect("Failed to find an appropiate adapter");
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                },
-                None,
-            )
+        let (device, queue) = request_device(&adapter)
             .await
             .expect("Failed to create device");
 
@@ -53,31 +133,83 @@ impl Context {
 
         let swap_chain = device.create_swap_chain(&surface, &swap_chain_desc);
 
-        let uniform_layouts = UniformLayouts::new(&device);
+        let uniform_layouts = Rc::new(UniformLayouts::new(&device));
 
-        let uniforms = UniformContext::new(&device, &queue, &uniform_layouts, &size);
+        let uniforms = UniformContext::new(&device, &uniform_layouts, &size);
 
         Context {
             size,
-            instance,
-            surface,
-            adapter,
             device,
             queue,
-            swap_chain_desc,
-            swap_chain,
+            format: swap_chain_desc.format,
+            target: RenderTarget::Window {
+                surface,
+                swap_chain_desc,
+                swap_chain,
+            },
             uniforms,
             uniform_layouts,
         }
     }
 
+    // Context without a window, rendering into a texture of `size`. Takes any adapter
+    // when none is offered by default, e.g. a software rasterizer on machines without a
+    // GPU.
+    pub async fn new_headless(size: PhysicalSize<u32>) -> Result<Self, ContextError> {
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+
+        let adapter = match instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+            })
+            .await
+        {
+            Some(adapter) => adapter,
+            None => instance
+                .enumerate_adapters(wgpu::BackendBit::all())
+                .next()
+                .ok_or(ContextError::NoAdapter)?,
+        };
+
+        let (device, queue) = request_device(&adapter)
+            .await
+            .map_err(ContextError::RequestDevice)?;
+
+        let target = create_offscreen_target(&device, size);
+
+        let uniform_layouts = Rc::new(UniformLayouts::new(&device));
+
+        let uniforms = UniformContext::new(&device, &uniform_layouts, &size);
+
+        Ok(Context {
+            size,
+            device,
+            queue,
+            format: OFFSCREEN_FORMAT,
+            target,
+            uniforms,
+            uniform_layouts,
+        })
+    }
+
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
         self.size = size;
-        self.swap_chain_desc.width = size.width;
-        self.swap_chain_desc.height = size.height;
-        self.swap_chain = self
-            .device
-            .create_swap_chain(&self.surface, &self.swap_chain_desc);
+
+        match &mut self.target {
+            RenderTarget::Window {
+                surface,
+                swap_chain_desc,
+                swap_chain,
+            } => {
+                swap_chain_desc.width = size.width;
+                swap_chain_desc.height = size.height;
+                *swap_chain = self.device.create_swap_chain(surface, swap_chain_desc);
+            }
+            RenderTarget::Offscreen { .. } => {
+                self.target = create_offscreen_target(&self.device, size);
+            }
+        }
 
         let depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
             size: wgpu::Extent3d {
@@ -100,4 +232,237 @@ impl Context {
     pub fn size(&self) -> PhysicalSize<u32> {
         self.size
     }
+
+    // Renders `scene` as seen by `camera` into the window or the offscreen texture.
+    pub fn render(
+        &mut self,
+        resource_manager: &ResourceManager,
+        scene: &Scene,
+        camera: DefaultKey,
+    ) {
+        match &mut self.target {
+            RenderTarget::Window { swap_chain, .. } => {
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+
+                super::render(
+                    &self.device,
+                    &self.queue,
+                    resource_manager,
+                    &frame.view,
+                    &mut self.uniforms,
+                    scene,
+                    camera,
+                );
+            }
+            RenderTarget::Offscreen { view, .. } => {
+                super::render(
+                    &self.device,
+                    &self.queue,
+                    resource_manager,
+                    view,
+                    &mut self.uniforms,
+                    scene,
+                    camera,
+                );
+            }
+        }
+    }
+
+    // Reads back the last frame of a headless context, blocking until the GPU is done
+    // with it.
+    pub fn capture(&self) -> Result<Capture, ContextError> {
+        let texture = match &self.target {
+            RenderTarget::Offscreen { texture, .. } => texture,
+            RenderTarget::Window { .. } => return Err(ContextError::NotOffscreen),
+        };
+
+        let (width, height) = (self.size.width, self.size.height);
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let bytes_per_row = (width * 4).div_ceil(alignment) * alignment;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row,
+                    rows_per_image: height,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+
+        self.device.poll(wgpu::Maintain::Wait);
+
+        pollster::block_on(mapping).map_err(ContextError::MapBuffer)?;
+
+        let capture =
+            Capture::from_padded_rows(width, height, bytes_per_row, &slice.get_mapped_range());
+
+        buffer.unmap();
+
+        Ok(capture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::{
+            material::PbrMaterial, CameraComponent, MeshComponent, MeshPrimitive,
+            TransformComponent,
+        },
+        gpu::Shading,
+        shapes::generate_quad,
+        systems::TransformSystem,
+    };
+
+    // Channels of a capture may be off by this much from the golden image, for
+    // differences in rounding between adapters.
+    const GOLDEN_TOLERANCE: i32 = 2;
+
+    // The tests pass without rendering anything where there is no adapter.
+    fn headless_context(size: PhysicalSize<u32>) -> Option<Context> {
+        match pollster::block_on(Context::new_headless(size)) {
+            Ok(context) => Some(context),
+            Err(ContextError::NoAdapter) => {
+                eprintln!("no graphics adapter, skipping");
+                None
+            }
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn headless_context_captures_frames() {
+        let size = PhysicalSize::new(64, 48);
+
+        let mut context = match headless_context(size) {
+            Some(context) => context,
+            None => return,
+        };
+
+        let resource_manager = ResourceManager::default();
+        let mut scene = Scene::new();
+
+        let camera = scene.create_entity(TransformComponent::default());
+        scene
+            .cameras
+            .insert(camera, CameraComponent::new(1.0, 64.0 / 48.0, 0.1, 100.0));
+
+        context.render(&resource_manager, &scene, camera);
+
+        let capture = context.capture().unwrap();
+
+        assert_eq!((capture.width, capture.height), (64, 48));
+        assert!(capture
+            .pixels
+            .chunks(4)
+            .all(|pixel| pixel == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn unlit_quad_matches_the_golden_image() {
+        let size = PhysicalSize::new(64, 32);
+
+        let mut context = match headless_context(size) {
+            Some(context) => context,
+            None => return,
+        };
+
+        let mut resource_manager = ResourceManager::default();
+        let mut scene = Scene::new();
+
+        let material = PbrMaterial {
+            color: glam::vec4(1.0, 0.0, 0.0, 1.0),
+            ..Default::default()
+        };
+
+        let mut mesh = MeshComponent::new();
+        mesh.primitives.push(MeshPrimitive {
+            geometry_id: resource_manager
+                .geometries
+                .insert(generate_quad(&context.device)),
+            pipeline_id: resource_manager.pipeline_for(
+                &context,
+                Shading::Unlit,
+                wgpu::PrimitiveTopology::TriangleList,
+                true,
+            ),
+            material,
+            material_bind_group_id: resource_manager.material_bind_group_for(&context, &material),
+        });
+
+        let quad = scene.create_entity(TransformComponent::default());
+        scene.meshes.insert(quad, mesh);
+
+        // twice as wide as high, so the quad covers exactly the middle half of the
+        // columns and no pixel center lies on one of its edges
+        let camera = scene.create_entity(TransformComponent {
+            translation: glam::vec3(0.0, 0.0, -5.0),
+            ..Default::default()
+        });
+        scene
+            .cameras
+            .insert(camera, CameraComponent::orthographic(1.0, 2.0, 0.1, 100.0));
+
+        TransformSystem::update(&mut scene);
+
+        context.render(&resource_manager, &scene, camera);
+
+        let capture = context.capture().unwrap();
+
+        let golden = image::load_from_memory(include_bytes!("golden/unlit_quad.png"))
+            .unwrap()
+            .to_rgba8();
+
+        assert_eq!((capture.width, capture.height), golden.dimensions());
+
+        let differing = capture
+            .pixels
+            .iter()
+            .zip(golden.as_raw())
+            .filter(|&(&a, &b)| (a as i32 - b as i32).abs() > GOLDEN_TOLERANCE)
+            .count();
+
+        if differing > 0 {
+            let path = std::env::temp_dir().join("unlit_quad.png");
+            capture.save(&path).unwrap();
+
+            panic!(
+                "{} channels differ from the golden image, the capture is at {}",
+                differing,
+                path.display()
+            );
+        }
+    }
 }
@@ -1,18 +1,75 @@
 use super::Vertex;
-use wgpu::{util::DeviceExt, Device};
+use std::rc::Rc;
+use wgpu::{util::DeviceExt, Device, Queue};
+
+// Offsets added to the base vertices, scaled by the weight of the target.
+#[derive(Debug, Default, Clone)]
+pub struct MorphTarget {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+}
+
+// Undeformed data of a geometry with morph targets, shared with its copies.
+pub struct MorphData {
+    pub vertex_data: Rc<Vec<Vertex>>,
+    pub index_data: Rc<Vec<u32>>,
+    pub targets: Rc<Vec<MorphTarget>>,
+    // weights the vertex buffer currently is blended with
+    pub weights: Vec<f32>,
+}
 
 pub struct Geometry {
     pub index_buffer: wgpu::Buffer,
     pub vertex_buffer: wgpu::Buffer,
     pub index_count: u32,
+    pub morph: Option<MorphData>,
+}
+
+pub fn blend_morph_targets(
+    vertex_data: &[Vertex],
+    targets: &[MorphTarget],
+    weights: &[f32],
+) -> Vec<Vertex> {
+    let mut out_data = vertex_data.to_vec();
+
+    for (target, &weight) in targets.iter().zip(weights) {
+        if weight == 0.0 {
+            continue;
+        }
+
+        for (vertex, delta) in out_data.iter_mut().zip(&target.positions) {
+            let position = glam::Vec3::from(vertex.position) + glam::Vec3::from(*delta) * weight;
+            vertex.position = position.into();
+        }
+
+        for (vertex, delta) in out_data.iter_mut().zip(&target.normals) {
+            let normal = glam::Vec3::from(vertex.normal) + glam::Vec3::from(*delta) * weight;
+            vertex.normal = normal.into();
+        }
+    }
+
+    for vertex in out_data.iter_mut() {
+        let normal = glam::Vec3::from(vertex.normal);
+
+        if normal.length() > 0.0 {
+            vertex.normal = normal.normalize().into();
+        }
+    }
+
+    out_data
 }
 
 impl Geometry {
-    pub fn new(device: &Device, vertex_data: &[Vertex], index_data: &[u32]) -> Self {
+    fn create_buffers(
+        device: &Device,
+        vertex_data: &[Vertex],
+        index_data: &[u32],
+        vertex_usage: wgpu::BufferUsage,
+    ) -> (wgpu::Buffer, wgpu::Buffer) {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
             contents: bytemuck::cast_slice(vertex_data),
-            usage: wgpu::BufferUsage::VERTEX,
+            usage: vertex_usage,
         });
 
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -21,10 +78,143 @@ impl Geometry {
             usage: wgpu::BufferUsage::INDEX,
         });
 
+        (vertex_buffer, index_buffer)
+    }
+
+    pub fn new(device: &Device, vertex_data: &[Vertex], index_data: &[u32]) -> Self {
+        let (vertex_buffer, index_buffer) =
+            Self::create_buffers(device, vertex_data, index_data, wgpu::BufferUsage::VERTEX);
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: index_data.len() as u32,
+            morph: None,
+        }
+    }
+
+    pub fn with_morph_targets(
+        device: &Device,
+        vertex_data: Vec<Vertex>,
+        index_data: Vec<u32>,
+        targets: Vec<MorphTarget>,
+    ) -> Self {
+        let (vertex_buffer, index_buffer) = Self::create_buffers(
+            device,
+            &vertex_data,
+            &index_data,
+            wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        );
+
         Self {
             vertex_buffer,
             index_buffer,
             index_count: index_data.len() as u32,
+            morph: Some(MorphData {
+                weights: vec![0.0; targets.len()],
+                vertex_data: Rc::new(vertex_data),
+                index_data: Rc::new(index_data),
+                targets: Rc::new(targets),
+            }),
         }
     }
+
+    // Copy with its own vertex buffer, which can be blended independently. Only
+    // geometries with morph targets keep the data to do so.
+    pub fn duplicate(&self, device: &Device) -> Option<Self> {
+        let morph = self.morph.as_ref()?;
+
+        let (vertex_buffer, index_buffer) = Self::create_buffers(
+            device,
+            &morph.vertex_data,
+            &morph.index_data,
+            wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        );
+
+        Some(Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: self.index_count,
+            morph: Some(MorphData {
+                vertex_data: morph.vertex_data.clone(),
+                index_data: morph.index_data.clone(),
+                targets: morph.targets.clone(),
+                weights: vec![0.0; morph.targets.len()],
+            }),
+        })
+    }
+
+    pub fn set_morph_weights(&mut self, queue: &Queue, weights: &[f32]) {
+        let morph = match &mut self.morph {
+            Some(morph) => morph,
+            None => return,
+        };
+
+        if morph.weights == weights {
+            return;
+        }
+
+        let vertex_data = blend_morph_targets(&morph.vertex_data, &morph.targets, weights);
+
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertex_data));
+
+        morph.weights = weights.to_vec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Vec<Vertex> {
+        vec![Vertex {
+            position: [1.0, 0.0, 0.0],
+            uv: [0.0; 2],
+            normal: [0.0, 0.0, 1.0],
+            uv1: [0.0; 2],
+            joints: [0; 4],
+            weights: [0.0; 4],
+        }]
+    }
+
+    fn targets() -> Vec<MorphTarget> {
+        vec![
+            MorphTarget {
+                positions: vec![[0.0, 2.0, 0.0]],
+                normals: vec![[0.0, 1.0, -1.0]],
+            },
+            MorphTarget {
+                positions: vec![[0.0, 0.0, 4.0]],
+                normals: Vec::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn zero_weights_return_the_base() {
+        let blended = blend_morph_targets(&base(), &targets(), &[0.0, 0.0]);
+
+        assert_eq!(blended[0].position, base()[0].position);
+        assert_eq!(blended[0].normal, base()[0].normal);
+    }
+
+    #[test]
+    fn a_full_weight_adds_the_whole_target() {
+        let blended = blend_morph_targets(&base(), &targets(), &[1.0, 0.0]);
+
+        assert_eq!(blended[0].position, [1.0, 2.0, 0.0]);
+        assert_eq!(blended[0].normal, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn weights_scale_and_add_up_the_targets() {
+        let blended = blend_morph_targets(&base(), &targets(), &[0.5, 0.25]);
+
+        assert_eq!(blended[0].position, [1.0, 1.0, 1.0]);
+
+        // the blended normal is renormalized
+        let normal = glam::Vec3::from(blended[0].normal);
+
+        assert!(normal.abs_diff_eq(glam::vec3(0.0, 1.0, 1.0).normalize(), 1e-5));
+    }
 }
@@ -0,0 +1,206 @@
+use smallvec::SmallVec;
+
+pub fn mip_level_count(size: (u32, u32)) -> u32 {
+    32 - size.0.max(size.1).max(1).leading_zeros()
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Source texels an output texel covers along one axis, weighted by how much of them
+// it covers. Odd sizes don't halve evenly, so the texels between two output texels
+// are split between them instead of being dropped.
+fn footprints(size: usize, out_size: usize) -> Vec<SmallVec<[(usize, f32); 3]>> {
+    let scale = size as f32 / out_size as f32;
+
+    (0..out_size)
+        .map(|i| {
+            let (start, end) = (i as f32 * scale, (i + 1) as f32 * scale);
+
+            (start.floor() as usize..(end.ceil() as usize).min(size))
+                .map(|j| {
+                    let covered = end.min(j as f32 + 1.0) - start.max(j as f32);
+
+                    (j, covered / scale)
+                })
+                .filter(|&(_, weight)| weight > 0.0)
+                .collect()
+        })
+        .collect()
+}
+
+fn downsample(
+    size: (u32, u32),
+    channels: usize,
+    decode: &[f32; 256],
+    srgb_channels: usize,
+    srgb: bool,
+    data: &[u8],
+) -> Vec<u8> {
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    let (out_width, out_height) = ((width / 2).max(1), (height / 2).max(1));
+
+    let x_footprints = footprints(width, out_width);
+    let y_footprints = footprints(height, out_height);
+
+    let mut out_data = Vec::with_capacity(out_width * out_height * channels);
+
+    for y_footprint in &y_footprints {
+        for x_footprint in &x_footprints {
+            for channel in 0..channels {
+                let is_srgb = srgb && channel < srgb_channels;
+
+                let mut average = 0.0;
+
+                for &(sy, y_weight) in y_footprint {
+                    for &(sx, x_weight) in x_footprint {
+                        let value = data[(sy * width + sx) * channels + channel];
+
+                        let value = if is_srgb {
+                            decode[value as usize]
+                        } else {
+                            value as f32 / 255.0
+                        };
+
+                        average += value * x_weight * y_weight;
+                    }
+                }
+
+                let encoded = if is_srgb {
+                    linear_to_srgb(average)
+                } else {
+                    average
+                };
+
+                out_data.push((encoded * 255.0).round().clamp(0.0, 255.0) as u8);
+            }
+        }
+    }
+
+    out_data
+}
+
+// Builds the full mip chain for 8 bit per channel images, laid out level after level
+// the way `create_texture_with_data` expects it. sRGB data is averaged in linear space,
+// alpha of four channel images always is linear.
+pub fn generate_mipmaps(size: (u32, u32), channels: usize, srgb: bool, data: &[u8]) -> Vec<u8> {
+    let mut decode = [0.0; 256];
+    for (i, value) in decode.iter_mut().enumerate() {
+        *value = srgb_to_linear(i as f32 / 255.0);
+    }
+
+    let srgb_channels = if channels == 4 { 3 } else { channels };
+
+    let mut out_data = data.to_vec();
+    let mut level_start = 0;
+    let mut level_size = size;
+
+    for _ in 1..mip_level_count(size) {
+        let level = downsample(
+            level_size,
+            channels,
+            &decode,
+            srgb_channels,
+            srgb,
+            &out_data[level_start..],
+        );
+
+        level_start = out_data.len();
+        level_size = ((level_size.0 / 2).max(1), (level_size.1 / 2).max(1));
+
+        out_data.extend(level);
+    }
+
+    out_data
+}
+
+// Channel count and color space of the formats mip chains can be generated for.
+pub fn mipmap_layout(format: wgpu::TextureFormat) -> Option<(usize, bool)> {
+    match format {
+        wgpu::TextureFormat::R8Unorm => Some((1, false)),
+        wgpu::TextureFormat::Rg8Unorm => Some((2, false)),
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Bgra8Unorm => Some((4, false)),
+        wgpu::TextureFormat::Rgba8UnormSrgb | wgpu::TextureFormat::Bgra8UnormSrgb => {
+            Some((4, true))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_level_count_goes_down_to_one_texel() {
+        assert_eq!(mip_level_count((1, 1)), 1);
+        assert_eq!(mip_level_count((256, 256)), 9);
+        assert_eq!(mip_level_count((256, 1)), 9);
+        assert_eq!(mip_level_count((5, 3)), 3);
+        assert_eq!(mip_level_count((300, 20)), 9);
+    }
+
+    #[test]
+    fn srgb_channels_are_averaged_in_linear_space() {
+        let data = [0, 0, 0, 0, 255, 255, 255, 255];
+
+        let srgb = generate_mipmaps((2, 1), 4, true, &data);
+        let linear = generate_mipmaps((2, 1), 4, false, &data);
+
+        let half = (linear_to_srgb(0.5) * 255.0).round() as u8;
+
+        assert_eq!(&srgb[data.len()..], &[half, half, half, 128]);
+        assert_eq!(&linear[data.len()..], &[128, 128, 128, 128]);
+    }
+
+    #[test]
+    fn odd_sizes_weight_the_shared_texels_in() {
+        let data = [0, 0, 250, 0, 0];
+
+        let mipmaps = generate_mipmaps((5, 1), 1, false, &data);
+
+        // 5 -> 2 -> 1, the middle texel is split between both texels of level 1
+        assert_eq!(mipmaps.len(), 5 + 2 + 1);
+        assert_eq!(&mipmaps[5..], &[50, 50, 50]);
+
+        let mipmaps = generate_mipmaps((3, 3), 1, false, &[0, 0, 0, 0, 0, 0, 0, 0, 255]);
+
+        assert_eq!(&mipmaps[9..], &[28]);
+    }
+
+    #[test]
+    fn levels_follow_each_other() {
+        let data: Vec<u8> = (0..4 * 4 * 2).map(|i| i as u8).collect();
+
+        let mipmaps = generate_mipmaps((4, 4), 2, false, &data);
+
+        assert_eq!(mipmaps.len(), (16 + 4 + 1) * 2);
+        assert_eq!(&mipmaps[..data.len()], &data[..]);
+    }
+
+    #[test]
+    fn mipmap_layout_knows_the_8_bit_formats() {
+        assert_eq!(
+            mipmap_layout(wgpu::TextureFormat::Rgba8UnormSrgb),
+            Some((4, true))
+        );
+        assert_eq!(
+            mipmap_layout(wgpu::TextureFormat::R8Unorm),
+            Some((1, false))
+        );
+        assert_eq!(mipmap_layout(wgpu::TextureFormat::Rgba16Float), None);
+    }
+}
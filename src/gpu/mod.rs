@@ -1,16 +1,21 @@
+pub mod capture;
 mod context;
 mod geometry;
+pub mod mipmap;
 mod pipeline;
 mod render;
+mod sampler;
+pub mod shadow;
 mod texture;
 pub mod uniform;
 mod vertex;
 
 pub use context::Context;
-pub use geometry::Geometry;
-pub use pipeline::Pipeline;
+pub use geometry::{Geometry, MorphTarget};
+pub use pipeline::{Pipeline, Shading};
 pub use render::render;
-pub use texture::Texture;
+pub use sampler::SamplerDesc;
+pub use texture::{create_material_bind_group, Texture};
 pub use vertex::Vertex;
 
 pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
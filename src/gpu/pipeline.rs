@@ -1,23 +1,89 @@
 use std::borrow::Cow;
 
 use super::{uniform::UniformLayouts, Vertex};
-use wgpu::{Device, SwapChainDescriptor};
+use wgpu::Device;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Shading {
+    // base color only
+    Unlit,
+    // metallic-roughness PBR lit by the lights of the scene
+    Lit,
+}
+
+impl Shading {
+    fn source(self) -> &'static str {
+        match self {
+            Shading::Unlit => include_str!("../shader/unlit.wgsl"),
+            Shading::Lit => include_str!("../shader/lit.wgsl"),
+        }
+    }
+}
+
+const FLOAT_SIZE: u64 = std::mem::size_of::<f32>() as u64;
+
+const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 6] = [
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float3,
+        offset: 0,
+        shader_location: 0,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float2,
+        offset: 3 * FLOAT_SIZE,
+        shader_location: 1,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float3,
+        offset: 5 * FLOAT_SIZE,
+        shader_location: 2,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float2,
+        offset: 8 * FLOAT_SIZE,
+        shader_location: 3,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Uint4,
+        offset: 10 * FLOAT_SIZE,
+        shader_location: 4,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float4,
+        offset: 14 * FLOAT_SIZE,
+        shader_location: 5,
+    },
+];
+
+// Layout of `Vertex`, shared by all pipelines drawing geometries.
+pub fn vertex_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &VERTEX_ATTRIBUTES,
+    }
+}
 
 pub struct Pipeline {
     pub pipeline: wgpu::RenderPipeline,
+    pub topology: wgpu::PrimitiveTopology,
 }
 
 impl Pipeline {
+    // Pipelines of double sided materials draw back faces too.
     pub fn new(
         device: &Device,
         uniform_layouts: &UniformLayouts,
-        sc_desc: &SwapChainDescriptor,
+        format: wgpu::TextureFormat,
+        shading: Shading,
+        topology: wgpu::PrimitiveTopology,
+        double_sided: bool,
     ) -> Self {
         let flags = wgpu::ShaderFlags::VALIDATION;
 
         let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
             label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shader/unlit.wgsl"))),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(shading.source())),
             flags,
         });
 
@@ -27,7 +93,7 @@ impl Pipeline {
                 &uniform_layouts.camera_bind_group_layout,
                 &uniform_layouts.transform_bind_group_layout,
                 &uniform_layouts.primitive_bind_group_layout,
-                &uniform_layouts.color_bind_group_layout,
+                &uniform_layouts.material_bind_group_layout,
             ],
             push_constant_ranges: &[],
         });
@@ -38,36 +104,21 @@ impl Pipeline {
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: "vs_main",
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::InputStepMode::Vertex,
-                    attributes: &[
-                        wgpu::VertexAttribute {
-                            format: wgpu::VertexFormat::Float3,
-                            offset: 0,
-                            shader_location: 0,
-                        },
-                        wgpu::VertexAttribute {
-                            format: wgpu::VertexFormat::Float2,
-                            offset: 3 * std::mem::size_of::<f32>() as u64,
-                            shader_location: 1,
-                        },
-                        wgpu::VertexAttribute {
-                            format: wgpu::VertexFormat::Float3,
-                            offset: 5 * std::mem::size_of::<f32>() as u64,
-                            shader_location: 2,
-                        },
-                    ],
-                }],
+                buffers: &[vertex_buffer_layout()],
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
                 entry_point: "fs_main",
-                targets: &[sc_desc.format.into()],
+                targets: &[format.into()],
             }),
             primitive: wgpu::PrimitiveState {
+                topology,
                 front_face: wgpu::FrontFace::Cw,
-                cull_mode: wgpu::CullMode::Back,
+                cull_mode: if double_sided {
+                    wgpu::CullMode::None
+                } else {
+                    wgpu::CullMode::Back
+                },
                 ..Default::default()
             },
             depth_stencil: Some(wgpu::DepthStencilState {
@@ -81,6 +132,6 @@ impl Pipeline {
             multisample: wgpu::MultisampleState::default(),
         });
 
-        Self { pipeline }
+        Self { pipeline, topology }
     }
 }
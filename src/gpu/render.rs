@@ -1,24 +1,147 @@
-use super::uniform::{
-    CameraUniformData, PrimitiveUniformData, TransformUniformData, UniformContext,
-    BUFFER_ENTITIES_NUM,
+use super::{
+    shadow::{
+        cascade_splits, cascade_view_proj, shadow_map_rect, spot_view_proj, CASCADE_COUNT,
+        SHADOW_DISTANCE,
+    },
+    uniform::{
+        CameraUniformData, JointUniformData, LightData, LightUniformData, PrimitiveUniformData,
+        ShadowUniformData, ShadowViewUniformData, TransformUniformData, UniformContext,
+        LIGHT_KIND_DIRECTIONAL, LIGHT_KIND_POINT, LIGHT_KIND_SPOT, MAX_LIGHTS, MAX_SHADOW_MAPS,
+    },
+};
+use crate::{
+    components::{
+        material::{AlphaMode, TextureInfo},
+        CameraComponent, LightKind, MeshComponent,
+    },
+    resources::{manager::ResourceManager, scene::Scene},
 };
-use crate::resources::{manager::ResourceManager, scene::Scene};
 use slotmap::DefaultKey;
-use wgpu::{Device, Queue, SwapChain};
+use smallvec::SmallVec;
+use wgpu::{Device, Queue, TextureView};
+
+const AMBIENT_LIGHT: glam::Vec4 = glam::const_vec4!([0.03, 0.03, 0.03, 1.0]);
+
+// Fills the light uniform and assigns the shadow maps. The cascades of the first shadow
+// casting directional light come first, spot lights get the remaining shadow maps.
+// Returns the number of shadow maps in use.
+fn light_uniform_data(
+    scene: &Scene,
+    camera: &CameraComponent,
+    camera_world: &glam::Mat4,
+) -> (LightUniformData, ShadowUniformData, usize) {
+    let mut light_data = LightUniformData {
+        ambient: AMBIENT_LIGHT,
+        ..Default::default()
+    };
+
+    let mut shadow_data = ShadowUniformData::default();
+
+    // lights beyond MAX_LIGHTS are dropped
+    let lights: Vec<_> = scene
+        .lights
+        .iter()
+        .filter_map(|(entity, light)| {
+            scene
+                .transforms
+                .get(entity)
+                .map(|transform| (light, transform.world))
+        })
+        .take(MAX_LIGHTS)
+        .collect();
+
+    let main_light = lights
+        .iter()
+        .position(|(light, _)| light.cast_shadows && matches!(light.kind, LightKind::Directional));
+
+    let mut shadow_map_count = if main_light.is_some() {
+        CASCADE_COUNT
+    } else {
+        0
+    };
+
+    for (i, (light, world)) in lights.into_iter().enumerate() {
+        let (kind, inner_cone_cos, outer_cone_cos) = match light.kind {
+            LightKind::Directional => (LIGHT_KIND_DIRECTIONAL, 1.0, 1.0),
+            LightKind::Point => (LIGHT_KIND_POINT, 1.0, 1.0),
+            LightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => (
+                LIGHT_KIND_SPOT,
+                inner_cone_angle.cos(),
+                outer_cone_angle.cos(),
+            ),
+        };
+
+        let position = world.w_axis.truncate();
+        let direction = world.transform_vector3(glam::Vec3::unit_z()).normalize();
+
+        let mut data = LightData {
+            position: world.w_axis,
+            direction: direction.extend(0.0),
+            color: (light.color * light.intensity).extend(1.0),
+            kind,
+            range: light.range.unwrap_or(0.0),
+            inner_cone_cos,
+            outer_cone_cos,
+            ..Default::default()
+        };
+
+        match light.kind {
+            LightKind::Directional if main_light == Some(i) => {
+                let near = camera.near.max(0.01);
+                let splits = cascade_splits(near, camera.far.min(SHADOW_DISTANCE).max(near));
+
+                for (view_proj, split) in shadow_data.view_projs.iter_mut().zip(splits.windows(2)) {
+                    *view_proj =
+                        cascade_view_proj(camera, camera_world, split[0], split[1], direction);
+                }
+
+                data.shadow_map = 0;
+                data.shadow_map_count = CASCADE_COUNT as u32;
+            }
+            LightKind::Spot {
+                outer_cone_angle, ..
+            } if light.cast_shadows && shadow_map_count < MAX_SHADOW_MAPS => {
+                shadow_data.view_projs[shadow_map_count] =
+                    spot_view_proj(position, direction, outer_cone_angle, light.range);
+
+                data.shadow_map = shadow_map_count as u32;
+                data.shadow_map_count = 1;
+
+                shadow_map_count += 1;
+            }
+            _ => {}
+        }
+
+        light_data.lights[light_data.light_count as usize] = data;
+        light_data.light_count += 1;
+    }
+
+    (light_data, shadow_data, shadow_map_count)
+}
+
+struct MeshDraw<'a> {
+    mesh: &'a MeshComponent,
+    transform_offset: wgpu::DynamicOffset,
+    joint_offset: wgpu::DynamicOffset,
+    primitive_offsets: SmallVec<[wgpu::DynamicOffset; 4]>,
+}
 
 pub fn render(
     device: &Device,
     queue: &Queue,
     resource_manager: &ResourceManager,
-    swap_chain: &mut SwapChain,
-    uniforms: &UniformContext,
+    target: &TextureView,
+    uniforms: &mut UniformContext,
     scene: &Scene,
     camera: DefaultKey,
 ) {
-    let view_proj = {
-        let camera_transform = scene.transforms.get(camera).unwrap();
-        let camera_comp = scene.cameras.get(camera).unwrap();
+    let camera_transform = scene.transforms.get(camera).unwrap();
+    let camera_comp = scene.cameras.get(camera).unwrap();
 
+    let view_proj = {
         let proj = camera_comp.proj;
         let view = camera_transform.world.inverse();
 
@@ -28,96 +151,230 @@ pub fn render(
     queue.write_buffer(
         &uniforms.camera_uniform_buffer,
         0,
-        bytemuck::bytes_of(&CameraUniformData { view_proj }),
+        bytemuck::bytes_of(&CameraUniformData {
+            view_proj,
+            position: camera_transform.world.w_axis,
+        }),
+    );
+
+    let (light_data, shadow_data, shadow_map_count) =
+        light_uniform_data(scene, camera_comp, &camera_transform.world);
+
+    queue.write_buffer(
+        &uniforms.light_uniform_buffer,
+        0,
+        bytemuck::bytes_of(&light_data),
     );
 
-    let frame = swap_chain
-        .get_current_frame()
-        .expect("Failed to acquire next swap chain texture")
-        .output;
+    queue.write_buffer(
+        &uniforms.shadow_uniform_buffer,
+        0,
+        bytemuck::bytes_of(&shadow_data),
+    );
+
+    // everything with dynamic offsets is collected first and uploaded with one write per
+    // buffer, the transforms and joints are shared by the shadow and the main pass
+    uniforms.shadow_view_staging.clear();
+    uniforms.transform_staging.clear();
+    uniforms.joint_staging.clear();
+    uniforms.primitive_staging.clear();
+
+    for &view_proj in shadow_data.view_projs.iter().take(shadow_map_count) {
+        uniforms
+            .shadow_view_staging
+            .push(&ShadowViewUniformData { view_proj });
+    }
+
+    // the first slot is used by meshes without a skin
+    uniforms.joint_staging.push(&JointUniformData::default());
+
+    let mut draws = Vec::new();
+
+    for (entitiy_id, mesh) in scene.meshes.iter() {
+        if let Some(transform) = scene.transforms.get(entitiy_id) {
+            let transform_offset = uniforms.transform_staging.push(&TransformUniformData {
+                model: transform.world,
+                normal: transform.world.inverse().transpose(),
+            });
+
+            let joint_offset = if let Some(skin) = scene.skins.get(entitiy_id) {
+                let inverse_world = transform.world.inverse();
+                let mut joint_data = JointUniformData::default();
+
+                for ((matrix, joint), inverse_bind_matrix) in joint_data
+                    .matrices
+                    .iter_mut()
+                    .zip(&skin.joints)
+                    .zip(&skin.inverse_bind_matrices)
+                {
+                    if let Some(joint_transform) = scene.transforms.get(*joint) {
+                        *matrix = inverse_world
+                            .mul_mat4(&joint_transform.world)
+                            .mul_mat4(inverse_bind_matrix);
+                    }
+                }
+
+                uniforms.joint_staging.push(&joint_data)
+            } else {
+                0
+            };
+
+            let mut primitive_offsets = SmallVec::new();
+
+            for primitive in &mesh.primitives {
+                let material = &primitive.material;
+                let tex_coord = |texture: Option<TextureInfo>| {
+                    texture.map_or(0, |texture_info| texture_info.tex_coord)
+                };
+
+                primitive_offsets.push(uniforms.primitive_staging.push(&PrimitiveUniformData {
+                    color: material.color,
+                    emissive: material.emissive.extend(0.0),
+                    color_tex_coord: tex_coord(material.color_texture),
+                    metallic: material.metallic,
+                    roughness: material.roughness,
+                    receive_shadows: mesh.receive_shadows as u32,
+                    metallic_roughness_tex_coord: tex_coord(material.metallic_roughness_texture),
+                    normal_tex_coord: tex_coord(material.normal_texture),
+                    occlusion_tex_coord: tex_coord(material.occlusion_texture),
+                    emissive_tex_coord: tex_coord(material.emissive_texture),
+                    normal_scale: if material.normal_texture.is_some() {
+                        material.normal_scale
+                    } else {
+                        0.0
+                    },
+                    occlusion_strength: material.occlusion_strength,
+                    alpha_cutoff: if material.alpha_mode == AlphaMode::Mask {
+                        material.alpha_cutoff
+                    } else {
+                        0.0
+                    },
+                    double_sided: material.double_sided as u32,
+                }));
+            }
+
+            draws.push(MeshDraw {
+                mesh,
+                transform_offset,
+                joint_offset,
+                primitive_offsets,
+            });
+        }
+    }
+
+    uniforms.upload(device, queue);
+
     let mut encoder =
         device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
     {
+        let shadow_pass = &uniforms.shadow_pass;
+
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
-            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                attachment: &frame.view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                    store: true,
-                },
-            }],
+            color_attachments: &[],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
-                attachment: &uniforms.depth_view,
+                attachment: &shadow_pass.atlas_view,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Clear(1.0),
-                    store: false,
+                    store: true,
                 }),
                 stencil_ops: None,
             }),
         });
 
-        let mut transform_counter: u32 = 0;
-        let mut primitive_counter: u32 = 0;
+        rpass.set_pipeline(&shadow_pass.pipeline);
 
-        for (entitiy_id, mesh) in scene.meshes.iter() {
-            if let Some(transform) = scene.transforms.get(entitiy_id) {
-                assert!(transform_counter < BUFFER_ENTITIES_NUM as wgpu::DynamicOffset);
+        for shadow_map in 0..shadow_map_count {
+            let (x, y, size) = shadow_map_rect(shadow_map);
 
-                let transform_offset: wgpu::DynamicOffset =
-                    transform_counter * wgpu::BIND_BUFFER_ALIGNMENT as wgpu::DynamicOffset;
+            rpass.set_viewport(x as f32, y as f32, size as f32, size as f32, 0.0, 1.0);
+            rpass.set_bind_group(
+                0,
+                &shadow_pass.view_bind_group,
+                &[shadow_map as wgpu::DynamicOffset
+                    * wgpu::BIND_BUFFER_ALIGNMENT as wgpu::DynamicOffset],
+            );
 
-                queue.write_buffer(
-                    &uniforms.transform_uniform_buffer,
-                    transform_offset as wgpu::BufferAddress,
-                    bytemuck::bytes_of(&TransformUniformData {
-                        model: transform.world,
-                    }),
+            for draw in draws.iter().filter(|draw| draw.mesh.cast_shadows) {
+                rpass.set_bind_group(
+                    1,
+                    &uniforms.transform_bind_group,
+                    &[draw.transform_offset, draw.joint_offset],
                 );
 
-                for primitive in &mesh.primitives {
-                    assert!(primitive_counter < BUFFER_ENTITIES_NUM as wgpu::DynamicOffset);
-
-                    let primitive_offset: wgpu::DynamicOffset =
-                        primitive_counter * wgpu::BIND_BUFFER_ALIGNMENT as wgpu::DynamicOffset;
-
-                    let geometry = resource_manager
-                        .geometries
-                        .get(primitive.geometry_id)
-                        .unwrap();
+                for primitive in &draw.mesh.primitives {
                     let pipeline = resource_manager
                         .pipelines
                         .get(primitive.pipeline_id)
                         .unwrap();
-                    if let Some(texture_key) = primitive.material.color_texture {
-                        let color_texture = resource_manager.texture.get(texture_key).unwrap();
 
-                        rpass.set_bind_group(3, &color_texture.bind_group, &[]);
-                    } else {
-                        rpass.set_bind_group(3, &uniforms.dummy_texture.bind_group, &[]);
+                    // lines and points don't cast shadows
+                    if pipeline.topology != wgpu::PrimitiveTopology::TriangleList {
+                        continue;
                     }
 
-                    queue.write_buffer(
-                        &uniforms.primitive_uniform_buffer,
-                        primitive_offset as wgpu::BufferAddress,
-                        bytemuck::bytes_of(&PrimitiveUniformData {
-                            color: primitive.material.color,
-                        }),
-                    );
-
-                    rpass.set_pipeline(&pipeline.pipeline);
-                    rpass.set_bind_group(0, &uniforms.camera_bind_group, &[]);
-                    rpass.set_bind_group(1, &uniforms.transform_bind_group, &[transform_offset]);
-                    rpass.set_bind_group(2, &uniforms.primitive_bind_group, &[primitive_offset]);
+                    let geometry = resource_manager
+                        .geometries
+                        .get(primitive.geometry_id)
+                        .unwrap();
+
                     rpass.set_index_buffer(geometry.index_buffer.slice(..), super::INDEX_FORMAT);
                     rpass.set_vertex_buffer(0, geometry.vertex_buffer.slice(..));
                     rpass.draw_indexed(0..geometry.index_count, 0, 0..1);
-
-                    primitive_counter += 1;
                 }
+            }
+        }
+    }
+    {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: &uniforms.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        for draw in &draws {
+            for (primitive, &primitive_offset) in
+                draw.mesh.primitives.iter().zip(&draw.primitive_offsets)
+            {
+                let geometry = resource_manager
+                    .geometries
+                    .get(primitive.geometry_id)
+                    .unwrap();
+                let pipeline = resource_manager
+                    .pipelines
+                    .get(primitive.pipeline_id)
+                    .unwrap();
+                let material_bind_group = resource_manager
+                    .material_bind_groups
+                    .get(primitive.material_bind_group_id)
+                    .unwrap();
 
-                transform_counter += 1;
+                rpass.set_pipeline(&pipeline.pipeline);
+                rpass.set_bind_group(0, &uniforms.camera_bind_group, &[]);
+                rpass.set_bind_group(
+                    1,
+                    &uniforms.transform_bind_group,
+                    &[draw.transform_offset, draw.joint_offset],
+                );
+                rpass.set_bind_group(2, &uniforms.primitive_bind_group, &[primitive_offset]);
+                rpass.set_bind_group(3, material_bind_group, &[]);
+                rpass.set_index_buffer(geometry.index_buffer.slice(..), super::INDEX_FORMAT);
+                rpass.set_vertex_buffer(0, geometry.vertex_buffer.slice(..));
+                rpass.draw_indexed(0..geometry.index_count, 0, 0..1);
             }
         }
     }
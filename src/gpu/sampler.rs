@@ -0,0 +1,39 @@
+use wgpu::{AddressMode, Device, FilterMode, Sampler};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    pub mipmaps: bool,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            mipmaps: true,
+        }
+    }
+}
+
+impl SamplerDesc {
+    pub fn create_sampler(&self, device: &Device) -> Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_max_clamp: if self.mipmaps { 100.0 } else { 0.0 },
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,295 @@
+use std::borrow::Cow;
+
+use super::{
+    pipeline::vertex_buffer_layout,
+    uniform::{ShadowViewUniformData, UniformLayouts, MAX_SHADOW_MAPS},
+    DEPTH_FORMAT,
+};
+use crate::components::{CameraComponent, Projection};
+use wgpu::{BindGroup, Buffer, Device, RenderPipeline, Sampler, TextureView};
+
+pub const SHADOW_ATLAS_SIZE: u32 = 4096;
+// The atlas is split into a grid of SHADOW_ATLAS_TILES x SHADOW_ATLAS_TILES shadow maps,
+// has to match the lit shader.
+pub const SHADOW_ATLAS_TILES: u32 = 4;
+pub const SHADOW_MAP_SIZE: u32 = SHADOW_ATLAS_SIZE / SHADOW_ATLAS_TILES;
+
+pub const CASCADE_COUNT: usize = 4;
+// Shadows of the main directional light end at this distance from the camera.
+pub const SHADOW_DISTANCE: f32 = 50.0;
+// How far towards the light casters outside of a cascade are still drawn.
+const CASTER_DISTANCE: f32 = 50.0;
+// Blend between logarithmic (1.0) and uniform (0.0) cascade splits.
+const SPLIT_LAMBDA: f32 = 0.75;
+const SPOT_NEAR: f32 = 0.05;
+
+// Everything needed to render the shadow maps into the atlas.
+pub struct ShadowPass {
+    pub atlas_view: TextureView,
+    pub sampler: Sampler,
+    pub view_uniform_buffer: Buffer,
+    pub view_bind_group: BindGroup,
+    pub pipeline: RenderPipeline,
+}
+
+impl ShadowPass {
+    pub fn new(device: &Device, uniform_layouts: &UniformLayouts) -> Self {
+        assert_eq!(
+            (SHADOW_ATLAS_TILES * SHADOW_ATLAS_TILES) as usize,
+            MAX_SHADOW_MAPS
+        );
+
+        let atlas_texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: SHADOW_ATLAS_SIZE,
+                height: SHADOW_ATLAS_SIZE,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+            label: None,
+        });
+
+        let atlas_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        assert!(std::mem::size_of::<ShadowViewUniformData>() as u64 <= wgpu::BIND_BUFFER_ALIGNMENT);
+
+        let view_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: MAX_SHADOW_MAPS as u64 * wgpu::BIND_BUFFER_ALIGNMENT,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let view_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_layouts.shadow_view_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &view_uniform_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(
+                        std::mem::size_of::<ShadowViewUniformData>() as wgpu::BufferAddress
+                    ),
+                },
+            }],
+            label: None,
+        });
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shader/shadow.wgsl"))),
+            flags: wgpu::ShaderFlags::VALIDATION,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[
+                &uniform_layouts.shadow_view_bind_group_layout,
+                &uniform_layouts.transform_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[vertex_buffer_layout()],
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: wgpu::CullMode::Back,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+                clamp_depth: false,
+            }),
+            multisample: wgpu::MultisampleState::default(),
+        });
+
+        Self {
+            atlas_view,
+            sampler,
+            view_uniform_buffer,
+            view_bind_group,
+            pipeline,
+        }
+    }
+}
+
+// Position and size in pixels of a shadow map in the atlas.
+pub fn shadow_map_rect(index: usize) -> (u32, u32, u32) {
+    let index = index as u32;
+
+    (
+        (index % SHADOW_ATLAS_TILES) * SHADOW_MAP_SIZE,
+        (index / SHADOW_ATLAS_TILES) * SHADOW_MAP_SIZE,
+        SHADOW_MAP_SIZE,
+    )
+}
+
+fn up_for(direction: glam::Vec3) -> glam::Vec3 {
+    if direction.y.abs() > 0.99 {
+        glam::Vec3::unit_x()
+    } else {
+        glam::Vec3::unit_y()
+    }
+}
+
+// View distances at which the cascades start and end.
+pub fn cascade_splits(near: f32, far: f32) -> [f32; CASCADE_COUNT + 1] {
+    let mut splits = [near; CASCADE_COUNT + 1];
+
+    for (i, split) in splits.iter_mut().enumerate().skip(1) {
+        let s = i as f32 / CASCADE_COUNT as f32;
+
+        let logarithmic = near * (far / near).powf(s);
+        let uniform = near + (far - near) * s;
+
+        *split = SPLIT_LAMBDA * logarithmic + (1.0 - SPLIT_LAMBDA) * uniform;
+    }
+
+    splits
+}
+
+// Orthographic view projection of a directional light covering the part of the camera
+// frustum between `near` and `far`. The covered volume only depends on the size of the
+// slice and moves in whole texels, so shadow edges don't shimmer as the camera moves.
+pub fn cascade_view_proj(
+    camera: &CameraComponent,
+    camera_world: &glam::Mat4,
+    near: f32,
+    far: f32,
+    direction: glam::Vec3,
+) -> glam::Mat4 {
+    let half_height = |distance: f32| match camera.projection {
+        Projection::Perspective => distance * (camera.fov / 2.0).tan(),
+        Projection::Orthographic { ymag } => ymag,
+    };
+
+    let mut corners = Vec::with_capacity(8);
+
+    for &distance in &[near, far] {
+        let y = half_height(distance);
+        let x = y * camera.aspect;
+
+        for &(sx, sy) in &[(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            corners.push(camera_world.transform_point3(glam::vec3(sx * x, sy * y, distance)));
+        }
+    }
+
+    let center = corners.iter().fold(glam::Vec3::zero(), |sum, &c| sum + c) / 8.0;
+
+    let radius = corners
+        .iter()
+        .map(|&corner| (corner - center).length())
+        .fold(0.0, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let up = up_for(direction);
+
+    let light_rotation = glam::Mat4::look_at_lh(glam::Vec3::zero(), direction, up);
+    let texel_size = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+
+    let mut light_center = light_rotation.transform_point3(center);
+    light_center.x = (light_center.x / texel_size).floor() * texel_size;
+    light_center.y = (light_center.y / texel_size).floor() * texel_size;
+
+    let center = light_rotation.inverse().transform_point3(light_center);
+
+    let eye = center - direction * (radius + CASTER_DISTANCE);
+    let view = glam::Mat4::look_at_lh(eye, center, up);
+    let proj = glam::Mat4::orthographic_lh(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        0.0,
+        2.0 * radius + CASTER_DISTANCE,
+    );
+
+    proj.mul_mat4(&view)
+}
+
+// Perspective view projection covering the cone of a spot light.
+pub fn spot_view_proj(
+    position: glam::Vec3,
+    direction: glam::Vec3,
+    outer_cone_angle: f32,
+    range: Option<f32>,
+) -> glam::Mat4 {
+    let fov = (2.0 * outer_cone_angle).min(std::f32::consts::PI - 0.01);
+    let far = range.unwrap_or(SHADOW_DISTANCE);
+
+    let view = glam::Mat4::look_at_lh(position, position + direction, up_for(direction));
+    let proj = glam::Mat4::perspective_lh(fov, 1.0, SPOT_NEAR, far);
+
+    proj.mul_mat4(&view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cascade_splits_cover_the_range() {
+        let splits = cascade_splits(0.1, 50.0);
+
+        assert_eq!(splits[0], 0.1);
+        assert!((splits[CASCADE_COUNT] - 50.0).abs() < 1e-3);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn cascades_contain_their_slice_of_the_frustum() {
+        let camera = CameraComponent::new(std::f32::consts::FRAC_PI_4, 1.5, 0.1, 100.0);
+        let camera_world = glam::Mat4::from_rotation_translation(
+            glam::Quat::from_rotation_y(0.3),
+            glam::vec3(3.0, 2.0, -5.0),
+        );
+        let direction = glam::vec3(0.3, -1.0, 0.2).normalize();
+
+        let view_proj = cascade_view_proj(&camera, &camera_world, 2.0, 10.0, direction);
+
+        for &z in &[2.0, 10.0] {
+            let y = z * (camera.fov / 2.0).tan();
+            let x = y * camera.aspect;
+
+            for &(sx, sy) in &[(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+                let world = camera_world.transform_point3(glam::vec3(sx * x, sy * y, z));
+                let clip = view_proj.mul_vec4(world.extend(1.0));
+                let ndc = clip.truncate() / clip.w;
+
+                assert!(ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0, "{:?}", ndc);
+                assert!(ndc.z >= 0.0 && ndc.z <= 1.0, "{:?}", ndc);
+            }
+        }
+    }
+}
@@ -1,20 +1,22 @@
-use wgpu::{util::DeviceExt, BindGroup, Device, Queue};
+use wgpu::{util::DeviceExt, BindGroup, Device, Queue, Sampler, TextureView};
 
-use super::uniform::UniformLayouts;
+use super::uniform::{UniformLayouts, MATERIAL_TEXTURE_COUNT};
 
 pub struct Texture {
-    pub texture: wgpu::Texture,
-    pub bind_group: BindGroup,
+    pub view: TextureView,
+    // index into the samplers of the resource manager
+    pub sampler_id: usize,
 }
 
 impl Texture {
     pub fn new(
         device: &Device,
         queue: &Queue,
-        uniform_layouts: &UniformLayouts,
         size: (u32, u32),
         format: wgpu::TextureFormat,
+        mip_level_count: u32,
         data: &[u8],
+        sampler_id: usize,
     ) -> Self {
         let texture_extent = wgpu::Extent3d {
             width: size.0,
@@ -27,45 +29,48 @@ impl Texture {
             &wgpu::TextureDescriptor {
                 label: None,
                 size: texture_extent,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format,
                 usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
             },
-            &data,
+            data,
         );
 
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        Self { view, sampler_id }
+    }
+}
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_layouts.color_bind_group_layout,
-            entries: &[
+// Binds the color, metallic-roughness, normal, occlusion and emissive textures of a
+// material together with their samplers.
+pub fn create_material_bind_group(
+    device: &Device,
+    uniform_layouts: &UniformLayouts,
+    textures: [(&TextureView, &Sampler); MATERIAL_TEXTURE_COUNT],
+) -> BindGroup {
+    let entries: Vec<wgpu::BindGroupEntry> = textures
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (view, sampler))| {
+            vec![
                 wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                    binding: i as u32 * 2,
+                    resource: wgpu::BindingResource::TextureView(view),
                 },
                 wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
+                    binding: i as u32 * 2 + 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
-            ],
-            label: None,
-        });
+            ]
+        })
+        .collect();
 
-        Self {
-            texture,
-            bind_group,
-        }
-    }
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_layouts.material_bind_group_layout,
+        entries: &entries,
+        label: None,
+    })
 }
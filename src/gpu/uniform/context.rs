@@ -1,97 +1,138 @@
-use super::{CameraUniformData, PrimitiveUniformData, TransformUniformData, UniformLayouts};
-use crate::gpu::{texture, DEPTH_FORMAT};
-use texture::Texture;
-use wgpu::{util::DeviceExt, BindGroup, Buffer, Device, Queue, TextureView};
+use super::{
+    CameraUniformData, JointUniformData, LightUniformData, PrimitiveUniformData, ShadowUniformData,
+    TransformUniformData, UniformLayouts, UniformStaging,
+};
+use crate::gpu::{shadow::ShadowPass, DEPTH_FORMAT};
+use std::rc::Rc;
+use wgpu::{util::DeviceExt, BindGroup, Buffer, BufferAddress, Device, Queue, TextureView};
 use winit::dpi::PhysicalSize;
 
 pub struct UniformContext {
     pub camera_bind_group: BindGroup,
     pub camera_uniform_buffer: Buffer,
+    pub light_uniform_buffer: Buffer,
+    pub shadow_uniform_buffer: Buffer,
+    pub shadow_pass: ShadowPass,
     pub transform_bind_group: BindGroup,
     pub transform_uniform_buffer: Buffer,
+    pub joint_uniform_buffer: Buffer,
     pub primitive_bind_group: BindGroup,
     pub primitive_uniform_buffer: Buffer,
+    // sizes of the buffers above, in bytes
+    transform_capacity: BufferAddress,
+    joint_capacity: BufferAddress,
+    primitive_capacity: BufferAddress,
+    pub transform_staging: UniformStaging,
+    pub joint_staging: UniformStaging,
+    pub primitive_staging: UniformStaging,
+    pub shadow_view_staging: UniformStaging,
     pub depth_view: TextureView,
-    pub dummy_texture: Texture,
+    // the bind groups above are recreated when their buffers grow
+    uniform_layouts: Rc<UniformLayouts>,
 }
 
 impl UniformContext {
     pub fn new(
         device: &Device,
-        queue: &Queue,
-        uniform_layouts: &UniformLayouts,
+        uniform_layouts: &Rc<UniformLayouts>,
         size: &PhysicalSize<u32>,
     ) -> Self {
         let camera_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             contents: bytemuck::bytes_of(&CameraUniformData {
                 view_proj: glam::Mat4::identity(),
+                position: glam::Vec4::unit_w(),
             }),
             usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
         });
 
-        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_layouts.camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer {
-                    buffer: &camera_uniform_buffer,
-                    offset: 0,
-                    size: wgpu::BufferSize::new(
-                        std::mem::size_of::<CameraUniformData>() as wgpu::BufferAddress
-                    ),
-                },
-            }],
+        let light_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
+            contents: bytemuck::bytes_of(&LightUniformData::default()),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
         });
 
-        assert!(std::mem::size_of::<TransformUniformData>() as u64 <= wgpu::BIND_BUFFER_ALIGNMENT);
-
-        let transform_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        let shadow_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
-            size: super::BUFFER_ENTITIES_NUM * wgpu::BIND_BUFFER_ALIGNMENT,
+            contents: bytemuck::bytes_of(&ShadowUniformData::default()),
             usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-            mapped_at_creation: false,
         });
 
-        let transform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_layouts.transform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer {
-                    buffer: &transform_uniform_buffer,
-                    offset: 0,
-                    size: wgpu::BufferSize::new(
-                        std::mem::size_of::<TransformUniformData>() as wgpu::BufferAddress
-                    ),
+        let shadow_pass = ShadowPass::new(device, uniform_layouts);
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_layouts.camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: &camera_uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(
+                            std::mem::size_of::<CameraUniformData>() as wgpu::BufferAddress
+                        ),
+                    },
                 },
-            }],
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: &light_uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(
+                            std::mem::size_of::<LightUniformData>() as wgpu::BufferAddress
+                        ),
+                    },
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: &shadow_uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(
+                            std::mem::size_of::<ShadowUniformData>() as wgpu::BufferAddress
+                        ),
+                    },
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&shadow_pass.atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&shadow_pass.sampler),
+                },
+            ],
             label: None,
         });
 
+        assert!(std::mem::size_of::<TransformUniformData>() as u64 <= wgpu::BIND_BUFFER_ALIGNMENT);
+
+        let transform_capacity = super::INITIAL_ENTITIES_NUM * wgpu::BIND_BUFFER_ALIGNMENT;
+        let transform_uniform_buffer = create_uniform_buffer(device, transform_capacity);
+
+        assert_eq!(
+            std::mem::size_of::<JointUniformData>() as u64 % wgpu::BIND_BUFFER_ALIGNMENT,
+            0
+        );
+
+        let joint_capacity =
+            super::INITIAL_SKINS_NUM * std::mem::size_of::<JointUniformData>() as u64;
+        let joint_uniform_buffer = create_uniform_buffer(device, joint_capacity);
+
+        let transform_bind_group = create_transform_bind_group(
+            device,
+            uniform_layouts,
+            &transform_uniform_buffer,
+            &joint_uniform_buffer,
+        );
+
         assert!(std::mem::size_of::<PrimitiveUniformData>() as u64 <= wgpu::BIND_BUFFER_ALIGNMENT);
 
-        let primitive_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: super::BUFFER_ENTITIES_NUM * wgpu::BIND_BUFFER_ALIGNMENT,
-            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let primitive_capacity = super::INITIAL_ENTITIES_NUM * wgpu::BIND_BUFFER_ALIGNMENT;
+        let primitive_uniform_buffer = create_uniform_buffer(device, primitive_capacity);
 
-        let primitive_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_layouts.primitive_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer {
-                    buffer: &primitive_uniform_buffer,
-                    offset: 0,
-                    size: wgpu::BufferSize::new(
-                        std::mem::size_of::<PrimitiveUniformData>() as wgpu::BufferAddress
-                    ),
-                },
-            }],
-            label: None,
-        });
+        let primitive_bind_group =
+            create_primitive_bind_group(device, uniform_layouts, &primitive_uniform_buffer);
 
         let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
             size: wgpu::Extent3d {
@@ -109,24 +150,143 @@ impl UniformContext {
 
         let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let dummy_texture = Texture::new(
-            device,
-            queue,
-            uniform_layouts,
-            (32, 32),
-            wgpu::TextureFormat::Rgba8UnormSrgb,
-            &vec![255; 32 * 32 * 4],
-        );
-
         Self {
             camera_bind_group,
             camera_uniform_buffer,
+            light_uniform_buffer,
+            shadow_uniform_buffer,
+            shadow_pass,
             transform_bind_group,
             transform_uniform_buffer,
+            joint_uniform_buffer,
             primitive_bind_group,
             primitive_uniform_buffer,
+            transform_capacity,
+            joint_capacity,
+            primitive_capacity,
+            transform_staging: UniformStaging::new(wgpu::BIND_BUFFER_ALIGNMENT),
+            joint_staging: UniformStaging::new(
+                std::mem::size_of::<JointUniformData>() as wgpu::BufferAddress
+            ),
+            primitive_staging: UniformStaging::new(wgpu::BIND_BUFFER_ALIGNMENT),
+            shadow_view_staging: UniformStaging::new(wgpu::BIND_BUFFER_ALIGNMENT),
             depth_view,
-            dummy_texture,
+            uniform_layouts: uniform_layouts.clone(),
+        }
+    }
+
+    // Uploads the staged shadow views, transforms, joints and primitives with one write
+    // per buffer. Buffers which are too small for this frame are replaced with bigger
+    // ones, together with the bind groups using them.
+    pub fn upload(&mut self, device: &Device, queue: &Queue) {
+        let transform_capacity = self
+            .transform_staging
+            .grown_capacity(self.transform_capacity);
+        let joint_capacity = self.joint_staging.grown_capacity(self.joint_capacity);
+
+        if transform_capacity != self.transform_capacity || joint_capacity != self.joint_capacity {
+            if transform_capacity != self.transform_capacity {
+                self.transform_uniform_buffer = create_uniform_buffer(device, transform_capacity);
+                self.transform_capacity = transform_capacity;
+            }
+
+            if joint_capacity != self.joint_capacity {
+                self.joint_uniform_buffer = create_uniform_buffer(device, joint_capacity);
+                self.joint_capacity = joint_capacity;
+            }
+
+            self.transform_bind_group = create_transform_bind_group(
+                device,
+                &self.uniform_layouts,
+                &self.transform_uniform_buffer,
+                &self.joint_uniform_buffer,
+            );
         }
+
+        let primitive_capacity = self
+            .primitive_staging
+            .grown_capacity(self.primitive_capacity);
+
+        if primitive_capacity != self.primitive_capacity {
+            self.primitive_uniform_buffer = create_uniform_buffer(device, primitive_capacity);
+            self.primitive_capacity = primitive_capacity;
+            self.primitive_bind_group = create_primitive_bind_group(
+                device,
+                &self.uniform_layouts,
+                &self.primitive_uniform_buffer,
+            );
+        }
+
+        self.shadow_view_staging
+            .upload(queue, &self.shadow_pass.view_uniform_buffer);
+        self.transform_staging
+            .upload(queue, &self.transform_uniform_buffer);
+        self.joint_staging.upload(queue, &self.joint_uniform_buffer);
+        self.primitive_staging
+            .upload(queue, &self.primitive_uniform_buffer);
     }
 }
+
+fn create_uniform_buffer(device: &Device, size: BufferAddress) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_transform_bind_group(
+    device: &Device,
+    uniform_layouts: &UniformLayouts,
+    transform_uniform_buffer: &Buffer,
+    joint_uniform_buffer: &Buffer,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_layouts.transform_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: transform_uniform_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(
+                        std::mem::size_of::<TransformUniformData>() as wgpu::BufferAddress
+                    ),
+                },
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: joint_uniform_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(
+                        std::mem::size_of::<JointUniformData>() as wgpu::BufferAddress
+                    ),
+                },
+            },
+        ],
+        label: None,
+    })
+}
+
+fn create_primitive_bind_group(
+    device: &Device,
+    uniform_layouts: &UniformLayouts,
+    primitive_uniform_buffer: &Buffer,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_layouts.primitive_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer {
+                buffer: primitive_uniform_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(
+                    std::mem::size_of::<PrimitiveUniformData>() as wgpu::BufferAddress
+                ),
+            },
+        }],
+        label: None,
+    })
+}
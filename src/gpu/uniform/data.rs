@@ -1,9 +1,11 @@
+use super::{MAX_JOINTS, MAX_LIGHTS, MAX_SHADOW_MAPS};
 use bytemuck::{Pod, Zeroable};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct CameraUniformData {
     pub view_proj: glam::Mat4,
+    pub position: glam::Vec4,
 }
 
 unsafe impl Pod for CameraUniformData {}
@@ -13,6 +15,8 @@ unsafe impl Zeroable for CameraUniformData {}
 #[derive(Debug, Clone, Copy, Default)]
 pub struct TransformUniformData {
     pub model: glam::Mat4,
+    // inverse transpose of `model`, keeps normals perpendicular under non-uniform scale
+    pub normal: glam::Mat4,
 }
 
 unsafe impl Pod for TransformUniformData {}
@@ -22,7 +26,115 @@ unsafe impl Zeroable for TransformUniformData {}
 #[derive(Debug, Clone, Copy, Default)]
 pub struct PrimitiveUniformData {
     pub color: glam::Vec4,
+    pub emissive: glam::Vec4,
+    pub color_tex_coord: u32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub receive_shadows: u32,
+    pub metallic_roughness_tex_coord: u32,
+    pub normal_tex_coord: u32,
+    pub occlusion_tex_coord: u32,
+    pub emissive_tex_coord: u32,
+    // zero without a normal texture
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    // zero unless the material is alpha masked
+    pub alpha_cutoff: f32,
+    pub double_sided: u32,
 }
 
 unsafe impl Pod for PrimitiveUniformData {}
 unsafe impl Zeroable for PrimitiveUniformData {}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct JointUniformData {
+    pub matrices: [glam::Mat4; MAX_JOINTS],
+}
+
+impl Default for JointUniformData {
+    fn default() -> Self {
+        Self {
+            matrices: [glam::Mat4::identity(); MAX_JOINTS],
+        }
+    }
+}
+
+unsafe impl Pod for JointUniformData {}
+unsafe impl Zeroable for JointUniformData {}
+
+pub const LIGHT_KIND_DIRECTIONAL: u32 = 0;
+pub const LIGHT_KIND_POINT: u32 = 1;
+pub const LIGHT_KIND_SPOT: u32 = 2;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LightData {
+    pub position: glam::Vec4,
+    pub direction: glam::Vec4,
+    // premultiplied by the intensity
+    pub color: glam::Vec4,
+    pub kind: u32,
+    // zero for lights without a range
+    pub range: f32,
+    pub inner_cone_cos: f32,
+    pub outer_cone_cos: f32,
+    // consecutive shadow maps used by the light, one per cascade
+    pub shadow_map: u32,
+    pub shadow_map_count: u32,
+    pub _padding: [u32; 2],
+}
+
+unsafe impl Pod for LightData {}
+unsafe impl Zeroable for LightData {}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LightUniformData {
+    pub lights: [LightData; MAX_LIGHTS],
+    pub ambient: glam::Vec4,
+    pub light_count: u32,
+    pub _padding: [u32; 3],
+}
+
+impl Default for LightUniformData {
+    fn default() -> Self {
+        Self {
+            lights: [LightData::default(); MAX_LIGHTS],
+            ambient: glam::Vec4::zero(),
+            light_count: 0,
+            _padding: [0; 3],
+        }
+    }
+}
+
+unsafe impl Pod for LightUniformData {}
+unsafe impl Zeroable for LightUniformData {}
+
+// View projection of the shadow map currently rendered.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShadowViewUniformData {
+    pub view_proj: glam::Mat4,
+}
+
+unsafe impl Pod for ShadowViewUniformData {}
+unsafe impl Zeroable for ShadowViewUniformData {}
+
+// View projections of all shadow maps, indexed by `LightData::shadow_map`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowUniformData {
+    pub view_projs: [glam::Mat4; MAX_SHADOW_MAPS],
+}
+
+impl Default for ShadowUniformData {
+    fn default() -> Self {
+        Self {
+            view_projs: [glam::Mat4::identity(); MAX_SHADOW_MAPS],
+        }
+    }
+}
+
+unsafe impl Pod for ShadowUniformData {}
+unsafe impl Zeroable for ShadowUniformData {}
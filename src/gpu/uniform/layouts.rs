@@ -1,47 +1,114 @@
-use super::{CameraUniformData, PrimitiveUniformData, TransformUniformData};
+use super::{
+    CameraUniformData, JointUniformData, LightUniformData, PrimitiveUniformData, ShadowUniformData,
+    ShadowViewUniformData, TransformUniformData, MATERIAL_TEXTURE_COUNT,
+};
 use wgpu::{BindGroupLayout, Device};
 
 pub struct UniformLayouts {
     pub transform_bind_group_layout: BindGroupLayout,
     pub primitive_bind_group_layout: BindGroupLayout,
     pub camera_bind_group_layout: BindGroupLayout,
-    pub color_bind_group_layout: BindGroupLayout,
+    pub material_bind_group_layout: BindGroupLayout,
+    pub shadow_view_bind_group_layout: BindGroupLayout,
 }
 
 impl UniformLayouts {
     pub fn new(device: &Device) -> Self {
         let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStage::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new(
-                            std::mem::size_of::<CameraUniformData>() as wgpu::BufferAddress,
-                        ),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                CameraUniformData,
+                            >()
+                                as wgpu::BufferAddress),
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                LightUniformData,
+                            >()
+                                as wgpu::BufferAddress),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                ShadowUniformData,
+                            >()
+                                as wgpu::BufferAddress),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler {
+                            comparison: true,
+                            filtering: true,
+                        },
+                        count: None,
+                    },
+                ],
                 label: None,
             });
 
         let transform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStage::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: true,
-                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
-                            TransformUniformData,
-                        >()
-                            as wgpu::BufferAddress),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                TransformUniformData,
+                            >()
+                                as wgpu::BufferAddress),
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStage::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                JointUniformData,
+                            >()
+                                as wgpu::BufferAddress),
+                        },
+                        count: None,
+                    },
+                ],
                 label: None,
             });
 
@@ -63,11 +130,13 @@ impl UniformLayouts {
                 label: None,
             });
 
-        let color_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
+        // color, metallic-roughness, normal, occlusion and emissive textures, each followed
+        // by its sampler
+        let material_entries: Vec<wgpu::BindGroupLayoutEntry> = (0..MATERIAL_TEXTURE_COUNT as u32)
+            .flat_map(|i| {
+                vec![
                     wgpu::BindGroupLayoutEntry {
-                        binding: 0,
+                        binding: i * 2,
                         visibility: wgpu::ShaderStage::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
@@ -77,7 +146,7 @@ impl UniformLayouts {
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 1,
+                        binding: i * 2 + 1,
                         visibility: wgpu::ShaderStage::FRAGMENT,
                         ty: wgpu::BindingType::Sampler {
                             comparison: false,
@@ -85,7 +154,31 @@ impl UniformLayouts {
                         },
                         count: None,
                     },
-                ],
+                ]
+            })
+            .collect();
+
+        let material_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &material_entries,
+                label: None,
+            });
+
+        let shadow_view_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                            ShadowViewUniformData,
+                        >()
+                            as wgpu::BufferAddress),
+                    },
+                    count: None,
+                }],
                 label: None,
             });
 
@@ -93,7 +186,8 @@ impl UniformLayouts {
             transform_bind_group_layout,
             primitive_bind_group_layout,
             camera_bind_group_layout,
-            color_bind_group_layout,
+            material_bind_group_layout,
+            shadow_view_bind_group_layout,
         }
     }
 }
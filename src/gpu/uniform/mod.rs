@@ -1,11 +1,36 @@
 mod context;
 mod data;
 mod layouts;
+mod staging;
 
 pub use context::UniformContext;
 pub use data::CameraUniformData;
+pub use data::JointUniformData;
+pub use data::LightData;
+pub use data::LightUniformData;
 pub use data::PrimitiveUniformData;
+pub use data::ShadowUniformData;
+pub use data::ShadowViewUniformData;
 pub use data::TransformUniformData;
+pub use data::{LIGHT_KIND_DIRECTIONAL, LIGHT_KIND_POINT, LIGHT_KIND_SPOT};
 pub use layouts::UniformLayouts;
+pub use staging::UniformStaging;
 
-pub const BUFFER_ENTITIES_NUM: u64 = 1024;
+// Number of entities and skins the buffers bound with dynamic offsets have room for
+// initially, they grow when a frame needs more.
+pub const INITIAL_ENTITIES_NUM: u64 = 1024;
+pub const INITIAL_SKINS_NUM: u64 = 64;
+
+// Textures bound per material, has to match the bindings of group 3 in the lit shader.
+pub const MATERIAL_TEXTURE_COUNT: usize = 5;
+
+// Has to match the size of the joint matrix array in the shader.
+pub const MAX_JOINTS: usize = 64;
+
+// Has to match the size of the light array in the lit shader, lights beyond it are
+// ignored.
+pub const MAX_LIGHTS: usize = 16;
+
+// Tiles of the shadow atlas, has to match the size of the shadow map array in the lit
+// shader.
+pub const MAX_SHADOW_MAPS: usize = 16;
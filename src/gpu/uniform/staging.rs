@@ -0,0 +1,116 @@
+use bytemuck::Pod;
+use wgpu::{Buffer, BufferAddress, Queue};
+
+// CPU side copy of a uniform buffer bound with dynamic offsets, filled during a frame
+// and uploaded with a single write.
+pub struct UniformStaging {
+    data: Vec<u8>,
+    stride: usize,
+}
+
+impl UniformStaging {
+    // `stride` is the distance between elements, a multiple of the alignment of
+    // dynamic offsets.
+    pub fn new(stride: u64) -> Self {
+        assert_eq!(stride % wgpu::BIND_BUFFER_ALIGNMENT, 0);
+
+        Self {
+            data: Vec::new(),
+            stride: stride as usize,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.data.len() / self.stride
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    // Size in bytes of the staged data.
+    pub fn size(&self) -> BufferAddress {
+        self.data.len() as BufferAddress
+    }
+
+    // Size a buffer of `capacity` bytes has to grow to for the staged data to fit,
+    // doubling it so growing stops after a few frames.
+    pub fn grown_capacity(&self, capacity: BufferAddress) -> BufferAddress {
+        let mut grown = capacity.max(self.stride as BufferAddress);
+
+        while grown < self.size() {
+            grown *= 2;
+        }
+
+        grown
+    }
+
+    // Appends `value` and returns the dynamic offset to bind it with.
+    pub fn push<T: Pod>(&mut self, value: &T) -> wgpu::DynamicOffset {
+        let bytes = bytemuck::bytes_of(value);
+        assert!(bytes.len() <= self.stride);
+
+        let offset = self.data.len();
+
+        self.data.extend_from_slice(bytes);
+        self.data.resize(offset + self.stride, 0);
+
+        offset as wgpu::DynamicOffset
+    }
+
+    pub fn upload(&self, queue: &Queue, buffer: &Buffer) {
+        if !self.data.is_empty() {
+            queue.write_buffer(buffer, 0, &self.data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_pads_elements_to_the_stride() {
+        let mut staging = UniformStaging::new(wgpu::BIND_BUFFER_ALIGNMENT);
+
+        assert_eq!(staging.push(&[1.0f32; 4]), 0);
+        assert_eq!(
+            staging.push(&7u32),
+            wgpu::BIND_BUFFER_ALIGNMENT as wgpu::DynamicOffset
+        );
+        assert_eq!(staging.len(), 2);
+
+        let stride = wgpu::BIND_BUFFER_ALIGNMENT as usize;
+
+        assert_eq!(&staging.data[..4], &1.0f32.to_ne_bytes());
+        assert!(staging.data[16..stride].iter().all(|&byte| byte == 0));
+        assert_eq!(&staging.data[stride..stride + 4], &7u32.to_ne_bytes());
+        assert_eq!(staging.data.len(), 2 * stride);
+
+        staging.clear();
+
+        assert!(staging.is_empty());
+    }
+
+    #[test]
+    fn grown_capacity_doubles_until_everything_fits() {
+        let stride = wgpu::BIND_BUFFER_ALIGNMENT;
+        let mut staging = UniformStaging::new(stride);
+
+        assert_eq!(staging.grown_capacity(4 * stride), 4 * stride);
+
+        for _ in 0..5 {
+            staging.push(&0u32);
+        }
+
+        assert_eq!(staging.grown_capacity(4 * stride), 8 * stride);
+        assert_eq!(staging.grown_capacity(stride), 8 * stride);
+        assert_eq!(staging.grown_capacity(16 * stride), 16 * stride);
+    }
+}
@@ -6,6 +6,9 @@ pub struct Vertex {
     pub position: [f32; 3],
     pub uv: [f32; 2],
     pub normal: [f32; 3],
+    pub uv1: [f32; 2],
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
 }
 
 unsafe impl Pod for Vertex {}
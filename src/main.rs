@@ -1,15 +1,23 @@
 use components::TransformComponent;
 use components::*;
-use gpu::{Context, Pipeline};
+use gpu::Context;
 use input::InputManager;
 use physics::PhysicsWorld;
 use resources::{
+    assets::{Assets, PackSource},
+    hot_reload::MapReloader,
     manager::ResourceManager,
-    map::{Map, Node},
+    map::Map,
     scene::Scene,
 };
-use std::{f32::consts::PI, time::Instant};
-use systems::{PlayerSystem, TransformSystem};
+use slotmap::DefaultKey;
+use std::{
+    f32::consts::PI,
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use systems::{AnimationSystem, MorphSystem, PlayerSystem, TransformSystem};
 use winit::{
     event::{DeviceEvent, Event, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
@@ -24,57 +32,181 @@ mod resources;
 mod shapes;
 mod systems;
 
-async fn run(event_loop: EventLoop<()>, window: Window) {
-    let mut context = Context::new(&window).await;
+// Size of the frames rendered with `--capture`.
+const CAPTURE_SIZE: (u32, u32) = (1280, 720);
 
-    let mut scene = Scene::new();
+// Command line: `gemini [MAP] [--pack FILE] [--capture FILE] [--camera PATH]`, or
+// `gemini pack OUT FILE...` to write a pack file, see `write_pack`.
+#[derive(Debug, PartialEq)]
+struct Options {
+    map: String,
+    // pack file to read the assets from instead of the working directory
+    pack: Option<PathBuf>,
+    // renders a single frame without a window into this image and exits
+    capture: Option<PathBuf>,
+    // path of the entity whose camera the frame is captured with, see
+    // `Scene::find_by_path`
+    camera: Option<String>,
+}
 
-    let mut input_manager = InputManager::new();
-    let mut resource_manager = ResourceManager::default();
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Options {
+            map: String::from("assets/maps/test.json"),
+            pack: None,
+            capture: None,
+            camera: None,
+        };
 
-    let mut physics_world = PhysicsWorld::new();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("{} expects a value", arg))
+            };
 
-    let mut last_time = Instant::now();
+            match arg.as_str() {
+                "--pack" => options.pack = Some(value()?.into()),
+                "--capture" => options.capture = Some(value()?.into()),
+                "--camera" => options.camera = Some(value()?),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => options.map = arg,
+            }
+        }
+
+        Ok(options)
+    }
+}
 
-    let mut test_map = Map::default();
-    test_map
-        .prefabs
-        .push(String::from("assets/gltf/monkey.glb"));
+// Writes the files of `gemini pack OUT FILE...` into the pack file OUT. The files keep
+// the paths they are given with, so they should be relative to the working directory
+// the game runs in.
+fn write_pack<I: Iterator<Item = String>>(mut args: I) -> Result<(), String> {
+    let out = args
+        .next()
+        .ok_or_else(|| String::from("pack expects the output file and the files to pack"))?;
 
-    test_map.root.prefab_id = Some(0);
-    test_map.root.children.push(Node {
-        translation: glam::vec3(3.0, 0.0, 0.0),
-        prefab_id: Some(0),
+    let files = args
+        .map(|path| match std::fs::read(&path) {
+            Ok(data) => Ok((path, data)),
+            Err(err) => Err(format!("{}: {}", path, err)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if files.is_empty() {
+        return Err(String::from("pack expects at least one file to pack"));
+    }
+
+    std::fs::write(&out, PackSource::build(&files)).map_err(|err| format!("{}: {}", out, err))
+}
+
+fn exit_on_error<T, E: Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    })
+}
+
+fn add_camera(scene: &mut Scene, parent: Option<DefaultKey>, aspect: f32) -> DefaultKey {
+    let camera = scene.create_entity(TransformComponent {
+        parent,
         ..Default::default()
     });
 
     scene
-        .load_map(&context, &mut resource_manager, &test_map)
-        .unwrap();
-
-    resource_manager.pipelines.insert(Pipeline::new(
-        &context.device,
-        &context.uniform_layouts,
-        &context.swap_chain_desc,
-    ));
+        .cameras
+        .insert(camera, CameraComponent::new(PI / 4.0, aspect, 0.1, 100.0));
 
-    let player_entity = PlayerSystem::setup(&mut scene);
+    camera
+}
 
-    let camera = scene.create_entity(TransformComponent {
-        parent: Some(player_entity),
+// Sun shining down at an angle, for maps without lights of their own.
+fn add_sun(scene: &mut Scene) {
+    let sun = scene.create_entity(TransformComponent {
+        rotation: glam::Quat::from_rotation_x(PI / 3.0),
         ..Default::default()
     });
 
-    scene.cameras.insert(
-        camera,
-        CameraComponent::new(
-            PI / 4.0,
-            context.size().width as f32 / context.size().height as f32,
-            0.1,
-            100.0,
-        ),
+    scene.lights.insert(
+        sun,
+        LightComponent {
+            kind: LightKind::Directional,
+            intensity: 3.0,
+            ..Default::default()
+        },
+    );
+}
+
+// Renders the first frame of the map into `path`, waiting for all of its prefabs.
+#[cfg(not(target_arch = "wasm32"))]
+async fn capture(options: &Options, mut resource_manager: ResourceManager, path: &Path) {
+    let size = winit::dpi::PhysicalSize::new(CAPTURE_SIZE.0, CAPTURE_SIZE.1);
+    let aspect = size.width as f32 / size.height as f32;
+
+    let mut context = exit_on_error(Context::new_headless(size).await);
+
+    let mut scene = Scene::new();
+
+    let map = exit_on_error(Map::load(&**resource_manager.assets(), &options.map));
+    exit_on_error(scene.load_map(&context, &mut resource_manager, &map));
+
+    let camera = match &options.camera {
+        Some(camera_path) => {
+            let camera = scene
+                .find_by_path(camera_path)
+                .filter(|&entity| scene.cameras.contains_key(entity));
+
+            exit_on_error(camera.ok_or_else(|| format!("no camera at '{}'", camera_path)))
+        }
+        None => add_camera(&mut scene, None, aspect),
+    };
+
+    let camera_component = &mut scene.cameras[camera];
+    camera_component.aspect = aspect;
+    camera_component.update_projection_matrix();
+
+    add_sun(&mut scene);
+
+    TransformSystem::update(&mut scene);
+
+    context.render(&resource_manager, &scene, camera);
+
+    let frame = exit_on_error(context.capture());
+    exit_on_error(frame.save(path));
+}
+
+async fn run(
+    event_loop: EventLoop<()>,
+    window: Window,
+    options: Options,
+    mut resource_manager: ResourceManager,
+) {
+    let mut context = Context::new(&window).await;
+
+    let mut scene = Scene::new();
+
+    let mut input_manager = InputManager::new();
+
+    let mut physics_world = PhysicsWorld::new();
+
+    let mut last_time = Instant::now();
+
+    let mut map_reloader = exit_on_error(MapReloader::load(
+        &context,
+        &mut resource_manager,
+        &mut scene,
+        &options.map,
+    ));
+
+    let player_entity = PlayerSystem::setup(&mut scene);
+
+    let camera = add_camera(
+        &mut scene,
+        Some(player_entity),
+        context.size().width as f32 / context.size().height as f32,
     );
 
+    add_sun(&mut scene);
+
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
         match event {
@@ -103,10 +235,11 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
                 _ => {}
             },
-            Event::DeviceEvent { event, .. } => {
-                if let DeviceEvent::MouseMotion { delta } = event {
-                    input_manager.handle_mouse_event(delta);
-                }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                input_manager.handle_mouse_event(delta);
             }
 
             Event::RedrawRequested(_) => {
@@ -117,21 +250,27 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
                 physics_world.update(delta_time);
 
+                if let Err(err) = map_reloader.update(&context, &mut resource_manager, &mut scene) {
+                    eprintln!("error: {}", err);
+                }
+
+                if resource_manager.poll_prefabs(&context) {
+                    for (_, err) in scene.resolve_pending_prefabs(&mut resource_manager) {
+                        eprintln!("error: {}", err);
+                    }
+                }
+
                 PlayerSystem::update(&mut scene, &input_manager, player_entity, delta_time);
 
+                AnimationSystem::update(&mut scene, delta_time);
+
+                MorphSystem::update(&context, &mut resource_manager, &mut scene);
+
                 TransformSystem::update(&mut scene);
 
                 input_manager.late_update();
 
-                gpu::render(
-                    &context.device,
-                    &context.queue,
-                    &resource_manager,
-                    &mut context.swap_chain,
-                    &context.uniforms,
-                    &scene,
-                    camera,
-                );
+                context.render(&resource_manager, &scene, camera);
             }
             _ => {}
         }
@@ -139,6 +278,30 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 }
 
 fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+
+    if args.peek().map(String::as_str) == Some("pack") {
+        args.next();
+
+        return exit_on_error(write_pack(args));
+    }
+
+    let options = exit_on_error(Options::parse(args));
+
+    let mut resource_manager = ResourceManager::default();
+
+    if let Some(pack) = &options.pack {
+        let source = PackSource::open(pack).map_err(|err| format!("{}: {}", pack.display(), err));
+        resource_manager.set_assets(Assets::new(exit_on_error(source)));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if let Some(path) = &options.capture {
+            return pollster::block_on(capture(&options, resource_manager, path));
+        }
+    }
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_title("-")
@@ -151,7 +314,7 @@ fn main() {
 
     #[cfg(not(target_arch = "wasm32"))]
     {
-        pollster::block_on(run(event_loop, window));
+        pollster::block_on(run(event_loop, window, options, resource_manager));
     }
     #[cfg(target_arch = "wasm32")]
     {
@@ -166,6 +329,76 @@ fn main() {
                     .ok()
             })
             .expect("couldn't append canvas to document body");
-        wasm_bindgen_futures::spawn_local(run(event_loop, window));
+        wasm_bindgen_futures::spawn_local(run(event_loop, window, options, resource_manager));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resources::assets::AssetSource;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn options_default_to_the_test_map_in_a_window() {
+        let options = parse(&[]).unwrap();
+
+        assert_eq!(options.map, "assets/maps/test.json");
+        assert_eq!(options.pack, None);
+        assert_eq!(options.capture, None);
+        assert_eq!(options.camera, None);
+    }
+
+    #[test]
+    fn options_take_the_map_and_flag_values() {
+        let options = parse(&[
+            "--pack",
+            "game.pak",
+            "maps/town.json",
+            "--capture",
+            "town.png",
+            "--camera",
+            "town/gate/camera",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            options,
+            Options {
+                map: String::from("maps/town.json"),
+                pack: Some(PathBuf::from("game.pak")),
+                capture: Some(PathBuf::from("town.png")),
+                camera: Some(String::from("town/gate/camera")),
+            }
+        );
+    }
+
+    #[test]
+    fn written_packs_hold_the_given_files() {
+        let root = std::env::temp_dir().join(format!("gemini-pack-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+
+        let map = root.join("test.json");
+        let out = root.join("game.pak");
+        std::fs::write(&map, b"{}").unwrap();
+
+        let args = vec![out.display().to_string(), map.display().to_string()];
+        write_pack(args.into_iter()).unwrap();
+
+        let source = PackSource::open(&out).unwrap();
+        assert_eq!(source.read(&map).unwrap(), b"{}");
+
+        assert!(write_pack(vec![out.display().to_string()].into_iter()).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn options_reject_unknown_flags_and_missing_values() {
+        assert!(parse(&["--fullscreen"]).is_err());
+        assert!(parse(&["--capture"]).is_err());
     }
 }
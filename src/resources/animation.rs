@@ -0,0 +1,470 @@
+use std::ops::{Add, Mul};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    // Every keyframe stores an in-tangent, the value and an out-tangent, in this order.
+    CubicSpline,
+}
+
+#[derive(Debug, Clone)]
+pub enum Keyframes {
+    Translation(Vec<glam::Vec3>),
+    Rotation(Vec<glam::Quat>),
+    Scale(Vec<glam::Vec3>),
+    // `count` morph target weights per keyframe
+    Weights { count: usize, values: Vec<f32> },
+}
+
+// Animates one property of the entity at `target` in the targets of the playing
+// `AnimationComponent`.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub target: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub keyframes: Keyframes,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AnimationClip {
+    pub name: Option<String>,
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+// Returns the keyframe before `time` and how far `time` is towards the next one.
+fn find_keyframe(times: &[f32], time: f32) -> (usize, f32) {
+    let next = times.iter().position(|&t| t > time).unwrap_or(times.len());
+
+    if next == 0 {
+        return (0, 0.0);
+    }
+
+    if next == times.len() {
+        return (times.len() - 1, 0.0);
+    }
+
+    let previous = next - 1;
+    let span = times[next] - times[previous];
+
+    if span > 0.0 {
+        (previous, (time - times[previous]) / span)
+    } else {
+        (previous, 0.0)
+    }
+}
+
+fn hermite<T>(v0: T, b0: T, v1: T, a1: T, span: f32, s: f32) -> T
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    let s2 = s * s;
+    let s3 = s2 * s;
+
+    v0 * (2.0 * s3 - 3.0 * s2 + 1.0)
+        + b0 * ((s3 - 2.0 * s2 + s) * span)
+        + v1 * (-2.0 * s3 + 3.0 * s2)
+        + a1 * ((s3 - s2) * span)
+}
+
+// `value` returns the n-th value of the channel, which lets several properties share
+// one array of values.
+fn sample<T>(
+    times: &[f32],
+    value: impl Fn(usize) -> T,
+    interpolation: Interpolation,
+    time: f32,
+    lerp: fn(T, T, f32) -> T,
+) -> T
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    let (i, s) = find_keyframe(times, time);
+    let last = times.len() - 1;
+
+    match interpolation {
+        Interpolation::Step => value(i),
+        Interpolation::Linear if i == last => value(i),
+        Interpolation::Linear => lerp(value(i), value(i + 1), s),
+        Interpolation::CubicSpline if i == last => value(i * 3 + 1),
+        Interpolation::CubicSpline => hermite(
+            value(i * 3 + 1),
+            value(i * 3 + 2),
+            value((i + 1) * 3 + 1),
+            value((i + 1) * 3),
+            times[i + 1] - times[i],
+            s,
+        ),
+    }
+}
+
+pub fn sample_vec3(
+    times: &[f32],
+    values: &[glam::Vec3],
+    interpolation: Interpolation,
+    time: f32,
+) -> glam::Vec3 {
+    sample(
+        times,
+        |i| values[i],
+        interpolation,
+        time,
+        |a, b, s| a.lerp(b, s),
+    )
+}
+
+// Takes the shorter way around and always returns a unit quaternion.
+pub fn sample_quat(
+    times: &[f32],
+    values: &[glam::Quat],
+    interpolation: Interpolation,
+    time: f32,
+) -> glam::Quat {
+    let slerp = |a: glam::Quat, b: glam::Quat, s| {
+        if a.dot(b) < 0.0 {
+            a.slerp(-b, s)
+        } else {
+            a.slerp(b, s)
+        }
+    };
+
+    sample(times, |i| values[i], interpolation, time, slerp).normalize()
+}
+
+// Writes the `count` weights at `time` to `weights`, which keeps its allocation from one
+// frame to the next.
+pub fn sample_weights(
+    times: &[f32],
+    values: &[f32],
+    count: usize,
+    interpolation: Interpolation,
+    time: f32,
+    weights: &mut Vec<f32>,
+) {
+    weights.clear();
+    weights.extend((0..count).map(|n| {
+        sample(
+            times,
+            |i| values[i * count + n],
+            interpolation,
+            time,
+            |a, b, s| a + (b - a) * s,
+        )
+    }));
+}
+
+// Sampled value of a channel, applied to the translation, rotation, scale or morph
+// target weights of its target.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelValue {
+    Translation(glam::Vec3),
+    Rotation(glam::Quat),
+    Scale(glam::Vec3),
+    // the weights were written to the buffer passed to `Channel::sample`
+    Weights,
+}
+
+impl Channel {
+    pub fn sample(&self, time: f32, weights: &mut Vec<f32>) -> ChannelValue {
+        let times = &self.times;
+
+        match &self.keyframes {
+            Keyframes::Translation(values) => {
+                ChannelValue::Translation(sample_vec3(times, values, self.interpolation, time))
+            }
+            Keyframes::Rotation(values) => {
+                ChannelValue::Rotation(sample_quat(times, values, self.interpolation, time))
+            }
+            Keyframes::Scale(values) => {
+                ChannelValue::Scale(sample_vec3(times, values, self.interpolation, time))
+            }
+            Keyframes::Weights { count, values } => {
+                sample_weights(times, values, *count, self.interpolation, time, weights);
+
+                ChannelValue::Weights
+            }
+        }
+    }
+}
+
+// Range of clip time (start, end] that playback passed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl TimeRange {
+    pub fn contains(&self, time: f32) -> bool {
+        time > self.start && time <= self.end
+    }
+}
+
+// Moves `time` by `delta` and returns the new time together with the ranges passed on
+// the way, which are two when a looping clip wrapped around.
+pub fn advance_time(time: f32, delta: f32, duration: f32, looping: bool) -> (f32, Vec<TimeRange>) {
+    let end = time + delta;
+
+    if !looping || duration <= 0.0 {
+        let end = end.clamp(0.0, duration.max(0.0));
+
+        return (
+            end,
+            vec![TimeRange {
+                start: time.min(end),
+                end: time.max(end),
+            }],
+        );
+    }
+
+    let wrapped = end.rem_euclid(duration);
+
+    let passed = if delta >= 0.0 && end < duration {
+        vec![TimeRange { start: time, end }]
+    } else if delta >= 0.0 {
+        vec![
+            TimeRange {
+                start: time,
+                end: f32::INFINITY,
+            },
+            TimeRange {
+                start: f32::NEG_INFINITY,
+                end: wrapped,
+            },
+        ]
+    } else if end >= 0.0 {
+        vec![TimeRange {
+            start: end,
+            end: time,
+        }]
+    } else {
+        vec![
+            TimeRange {
+                start: f32::NEG_INFINITY,
+                end: time,
+            },
+            TimeRange {
+                start: wrapped,
+                end: f32::INFINITY,
+            },
+        ]
+    };
+
+    (wrapped, passed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_vec3_eq(a: glam::Vec3, b: glam::Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    fn assert_quat_eq(a: glam::Quat, b: glam::Quat) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn find_keyframe_clamps_to_the_ends() {
+        let times = [1.0, 2.0, 4.0];
+
+        assert_eq!(find_keyframe(&times, 0.0), (0, 0.0));
+        assert_eq!(find_keyframe(&times, 1.0), (0, 0.0));
+        assert_eq!(find_keyframe(&times, 3.0), (1, 0.5));
+        assert_eq!(find_keyframe(&times, 4.0), (2, 0.0));
+        assert_eq!(find_keyframe(&times, 5.0), (2, 0.0));
+    }
+
+    #[test]
+    fn linear_interpolates_between_keyframes() {
+        let times = [0.0, 1.0];
+        let values = [glam::Vec3::zero(), glam::vec3(2.0, 4.0, -2.0)];
+
+        assert_vec3_eq(
+            sample_vec3(&times, &values, Interpolation::Linear, 0.25),
+            glam::vec3(0.5, 1.0, -0.5),
+        );
+        assert_vec3_eq(
+            sample_vec3(&times, &values, Interpolation::Linear, 2.0),
+            values[1],
+        );
+    }
+
+    #[test]
+    fn step_holds_the_previous_keyframe() {
+        let times = [0.0, 1.0, 2.0];
+        let values = [
+            glam::Vec3::zero(),
+            glam::Vec3::one(),
+            glam::Vec3::splat(2.0),
+        ];
+
+        assert_vec3_eq(
+            sample_vec3(&times, &values, Interpolation::Step, 0.99),
+            values[0],
+        );
+        assert_vec3_eq(
+            sample_vec3(&times, &values, Interpolation::Step, 1.5),
+            values[1],
+        );
+    }
+
+    #[test]
+    fn cubic_spline_hits_keyframes_and_follows_tangents() {
+        let times = [0.0, 2.0];
+        // in-tangent, value, out-tangent per keyframe
+        let values = [
+            glam::Vec3::zero(),
+            glam::Vec3::zero(),
+            glam::Vec3::one(),
+            glam::Vec3::one(),
+            glam::Vec3::splat(2.0),
+            glam::Vec3::zero(),
+        ];
+
+        assert_vec3_eq(
+            sample_vec3(&times, &values, Interpolation::CubicSpline, 0.0),
+            glam::Vec3::zero(),
+        );
+        assert_vec3_eq(
+            sample_vec3(&times, &values, Interpolation::CubicSpline, 2.0),
+            glam::Vec3::splat(2.0),
+        );
+        // with tangents matching the slope the spline is a straight line
+        assert_vec3_eq(
+            sample_vec3(&times, &values, Interpolation::CubicSpline, 0.5),
+            glam::Vec3::splat(0.5),
+        );
+    }
+
+    #[test]
+    fn rotation_uses_the_shortest_path() {
+        let times = [0.0, 1.0];
+        let values = [
+            glam::Quat::identity(),
+            -glam::Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+        ];
+
+        let rotation = sample_quat(&times, &values, Interpolation::Linear, 0.5);
+
+        assert_quat_eq(
+            rotation,
+            glam::Quat::from_rotation_y(std::f32::consts::FRAC_PI_4),
+        );
+        assert!((rotation.length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn channel_sample_returns_the_animated_property() {
+        let channel = Channel {
+            target: 0,
+            interpolation: Interpolation::Linear,
+            times: vec![0.0, 1.0],
+            keyframes: Keyframes::Scale(vec![glam::Vec3::one(), glam::Vec3::splat(3.0)]),
+        };
+
+        assert_eq!(
+            channel.sample(0.5, &mut Vec::new()),
+            ChannelValue::Scale(glam::Vec3::splat(2.0))
+        );
+    }
+
+    #[test]
+    fn sample_weights_interpolates_every_weight() {
+        let times = [0.0, 1.0];
+        // two weights per keyframe
+        let values = [0.0, 1.0, 1.0, 0.0];
+        let mut weights = vec![9.0; 5];
+
+        sample_weights(&times, &values, 2, Interpolation::Linear, 0.0, &mut weights);
+        assert_eq!(weights, vec![0.0, 1.0]);
+
+        sample_weights(
+            &times,
+            &values,
+            2,
+            Interpolation::Linear,
+            0.25,
+            &mut weights,
+        );
+        assert_eq!(weights, vec![0.25, 0.75]);
+
+        sample_weights(&times, &values, 2, Interpolation::Step, 0.75, &mut weights);
+        assert_eq!(weights, vec![0.0, 1.0]);
+
+        sample_weights(&times, &values, 2, Interpolation::Linear, 2.0, &mut weights);
+        assert_eq!(weights, vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn sample_weights_reads_the_values_of_cubic_splines() {
+        let times = [0.0, 1.0];
+        // in-tangents, values and out-tangents of both weights per keyframe
+        let values = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0];
+        let mut weights = Vec::new();
+
+        sample_weights(
+            &times,
+            &values,
+            2,
+            Interpolation::CubicSpline,
+            0.0,
+            &mut weights,
+        );
+        assert_eq!(weights, vec![0.0, 1.0]);
+
+        sample_weights(
+            &times,
+            &values,
+            2,
+            Interpolation::CubicSpline,
+            0.5,
+            &mut weights,
+        );
+        assert_eq!(weights, vec![0.5, 0.5]);
+
+        sample_weights(
+            &times,
+            &values,
+            2,
+            Interpolation::CubicSpline,
+            1.0,
+            &mut weights,
+        );
+        assert_eq!(weights, vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn advance_time_clamps_clips_that_do_not_loop() {
+        let (time, passed) = advance_time(0.5, 1.0, 1.0, false);
+
+        assert_eq!(time, 1.0);
+        assert_eq!(
+            passed,
+            vec![TimeRange {
+                start: 0.5,
+                end: 1.0
+            }]
+        );
+    }
+
+    #[test]
+    fn advance_time_passes_events_when_wrapping() {
+        let (time, passed) = advance_time(0.75, 0.5, 1.0, true);
+
+        assert!((time - 0.25).abs() < 1e-5);
+        assert!(passed.iter().any(|range| range.contains(0.9)));
+        assert!(passed.iter().any(|range| range.contains(0.0)));
+        assert!(!passed.iter().any(|range| range.contains(0.5)));
+
+        let (time, passed) = advance_time(0.25, -0.5, 1.0, true);
+
+        assert!((time - 0.75).abs() < 1e-5);
+        assert!(passed.iter().any(|range| range.contains(0.1)));
+        assert!(passed.iter().any(|range| range.contains(0.9)));
+        assert!(!passed.iter().any(|range| range.contains(0.5)));
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    io,
+    ops::Range,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+// Where maps, models and everything they reference are read from. Paths are relative
+// to the root of the source and use '/' as separator.
+pub trait AssetSource: std::fmt::Debug + Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    // Used to detect changes for hot reloading, sources that can't change return None.
+    fn modified(&self, _path: &Path) -> Option<SystemTime> {
+        None
+    }
+}
+
+// Resolves "." and ".." components, so the same file always ends up with the same path.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} is not part of the asset source", path.display()),
+    )
+}
+
+// Loose files below a directory.
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_owned(),
+        }
+    }
+}
+
+impl AssetSource for DirectorySource {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(self.root.join(path))
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(self.root.join(path))
+            .and_then(|m| m.modified())
+            .ok()
+    }
+}
+
+// Files kept in memory, for tests.
+#[cfg(test)]
+#[derive(Debug, Default, Clone)]
+pub struct MemorySource {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+#[cfg(test)]
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<P: AsRef<Path>>(&mut self, path: P, data: Vec<u8>) {
+        self.files.insert(normalize_path(path.as_ref()), data);
+    }
+}
+
+#[cfg(test)]
+impl AssetSource for MemorySource {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .get(&normalize_path(path))
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+const PACK_MAGIC: &[u8; 4] = b"GPAK";
+const PACK_VERSION: u32 = 1;
+
+// All assets in a single file. The layout is the magic, the version and the number of
+// entries followed by the entries, each with the length of its path, the path, the
+// offset and the length of its data, and finally the data itself. All numbers are
+// little endian u32s.
+#[derive(Debug, Clone)]
+pub struct PackSource {
+    data: Vec<u8>,
+    entries: HashMap<PathBuf, Range<usize>>,
+}
+
+fn invalid_pack(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid pack file: {}", message),
+    )
+}
+
+fn read_u32(data: &[u8], offset: &mut usize) -> io::Result<u32> {
+    let bytes = data
+        .get(*offset..*offset + 4)
+        .ok_or_else(|| invalid_pack("unexpected end of file"))?;
+
+    *offset += 4;
+
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl PackSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Self> {
+        if !data.starts_with(PACK_MAGIC) {
+            return Err(invalid_pack("missing magic"));
+        }
+
+        let mut offset = PACK_MAGIC.len();
+
+        let version = read_u32(&data, &mut offset)?;
+
+        if version != PACK_VERSION {
+            return Err(invalid_pack("unsupported version"));
+        }
+
+        let entry_count = read_u32(&data, &mut offset)?;
+        let mut entries = HashMap::new();
+
+        for _ in 0..entry_count {
+            let path_len = read_u32(&data, &mut offset)? as usize;
+            let path_end = offset
+                .checked_add(path_len)
+                .ok_or_else(|| invalid_pack("bad entry path"))?;
+
+            let path = data
+                .get(offset..path_end)
+                .and_then(|path| std::str::from_utf8(path).ok())
+                .ok_or_else(|| invalid_pack("bad entry path"))?;
+
+            offset = path_end;
+
+            let start = read_u32(&data, &mut offset)? as usize;
+            let len = read_u32(&data, &mut offset)? as usize;
+
+            let end = match start.checked_add(len) {
+                Some(end) if end <= data.len() => end,
+                _ => return Err(invalid_pack("entry out of bounds")),
+            };
+
+            entries.insert(normalize_path(Path::new(path)), start..end);
+        }
+
+        Ok(Self { data, entries })
+    }
+
+    // Builds the contents of a pack file from (path, data) pairs.
+    pub fn build<P: AsRef<Path>>(files: &[(P, Vec<u8>)]) -> Vec<u8> {
+        let paths: Vec<String> = files
+            .iter()
+            .map(|(path, _)| {
+                normalize_path(path.as_ref())
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+
+        let header_len =
+            PACK_MAGIC.len() + 8 + paths.iter().map(|path| 12 + path.len()).sum::<usize>();
+
+        let mut out_data = Vec::with_capacity(
+            header_len + files.iter().map(|(_, data)| data.len()).sum::<usize>(),
+        );
+
+        out_data.extend_from_slice(PACK_MAGIC);
+        out_data.extend_from_slice(&PACK_VERSION.to_le_bytes());
+        out_data.extend_from_slice(&(files.len() as u32).to_le_bytes());
+
+        let mut data_offset = header_len;
+
+        for (path, (_, data)) in paths.iter().zip(files) {
+            out_data.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            out_data.extend_from_slice(path.as_bytes());
+            out_data.extend_from_slice(&(data_offset as u32).to_le_bytes());
+            out_data.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+            data_offset += data.len();
+        }
+
+        for (_, data) in files {
+            out_data.extend_from_slice(data);
+        }
+
+        out_data
+    }
+}
+
+impl AssetSource for PackSource {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.entries
+            .get(&normalize_path(path))
+            .map(|range| self.data[range.clone()].to_vec())
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+// Shared handle to an asset source, reading from the working directory by default.
+#[derive(Debug, Clone)]
+pub struct Assets(Arc<dyn AssetSource>);
+
+impl Assets {
+    pub fn new<S: AssetSource + 'static>(source: S) -> Self {
+        Self(Arc::new(source))
+    }
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Self::new(DirectorySource::new("."))
+    }
+}
+
+impl std::ops::Deref for Assets {
+    type Target = dyn AssetSource;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_path_resolves_dots() {
+        assert_eq!(
+            normalize_path(Path::new("./models/../textures/a.png")),
+            PathBuf::from("textures/a.png")
+        );
+        assert_eq!(
+            normalize_path(Path::new("../a.bin")),
+            PathBuf::from("../a.bin")
+        );
+    }
+
+    #[test]
+    fn memory_source_reads_inserted_files() {
+        let mut source = MemorySource::new();
+        source.insert("models/a.bin", vec![1, 2, 3]);
+
+        assert_eq!(
+            source.read(Path::new("models/./a.bin")).unwrap(),
+            vec![1, 2, 3]
+        );
+        assert!(source.read(Path::new("models/b.bin")).is_err());
+    }
+
+    #[test]
+    fn pack_source_reads_built_packs() {
+        let data = PackSource::build(&[
+            ("maps/test.json", b"{}".to_vec()),
+            ("models/a.bin", vec![4, 5, 6, 7]),
+        ]);
+
+        let source = PackSource::from_bytes(data).unwrap();
+
+        assert_eq!(source.read(Path::new("maps/test.json")).unwrap(), b"{}");
+        assert_eq!(
+            source.read(Path::new("models/a.bin")).unwrap(),
+            vec![4, 5, 6, 7]
+        );
+        assert!(source.read(Path::new("models/b.bin")).is_err());
+    }
+
+    #[test]
+    fn pack_source_rejects_other_files() {
+        assert!(PackSource::from_bytes(b"PK\x03\x04".to_vec()).is_err());
+        assert!(PackSource::from_bytes(b"GPAK\x01\x00\x00\x00\x01\x00\x00\x00".to_vec()).is_err());
+    }
+
+    #[test]
+    fn pack_source_rejects_entries_past_the_end() {
+        let mut data = PackSource::build(&[("a", vec![1, 2, 3])]);
+
+        // the data of the only entry starts at u32::MAX
+        let start = PACK_MAGIC.len() + 8 + 4 + 1;
+        data[start..start + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(PackSource::from_bytes(data).is_err());
+    }
+}
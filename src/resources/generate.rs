@@ -0,0 +1,285 @@
+use crate::gpu::Vertex;
+
+pub fn sequential_indices(vertex_count: usize) -> Vec<u32> {
+    (0..vertex_count as u32).collect()
+}
+
+fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> glam::Vec3 {
+    let a = glam::Vec3::from(a);
+    let b = glam::Vec3::from(b);
+    let c = glam::Vec3::from(c);
+
+    (b - a).cross(c - a)
+}
+
+fn normalize_or_zero(v: glam::Vec3) -> glam::Vec3 {
+    let length = v.length();
+
+    if length > 0.0 {
+        v / length
+    } else {
+        glam::Vec3::zero()
+    }
+}
+
+// Every triangle gets its own three vertices, so shared vertices are split up.
+pub fn flat_normals(vertex_data: &mut Vec<Vertex>, index_data: &mut Vec<u32>) {
+    let mut out_data = Vec::with_capacity(index_data.len());
+
+    for triangle in index_data.chunks_exact(3) {
+        let mut vertices = [
+            vertex_data[triangle[0] as usize],
+            vertex_data[triangle[1] as usize],
+            vertex_data[triangle[2] as usize],
+        ];
+
+        let normal = face_normal(
+            vertices[0].position,
+            vertices[1].position,
+            vertices[2].position,
+        );
+
+        for vertex in &mut vertices {
+            vertex.normal = normalize_or_zero(normal).into();
+        }
+
+        out_data.extend_from_slice(&vertices);
+    }
+
+    *vertex_data = out_data;
+    *index_data = sequential_indices(vertex_data.len());
+}
+
+// Face normals are accumulated unnormalized, which weights them by triangle area.
+pub fn smooth_normals(vertex_data: &mut [Vertex], index_data: &[u32]) {
+    let mut normals = vec![glam::Vec3::zero(); vertex_data.len()];
+
+    for triangle in index_data.chunks_exact(3) {
+        let normal = face_normal(
+            vertex_data[triangle[0] as usize].position,
+            vertex_data[triangle[1] as usize].position,
+            vertex_data[triangle[2] as usize].position,
+        );
+
+        for &index in triangle {
+            normals[index as usize] += normal;
+        }
+    }
+
+    for (vertex, normal) in vertex_data.iter_mut().zip(normals) {
+        vertex.normal = normalize_or_zero(normal).into();
+    }
+}
+
+// Projects the positions onto the plane spanned by the two largest extents of the
+// bounding box. Degenerate geometry ends up with zeroed coordinates.
+pub fn planar_uvs(vertex_data: &mut [Vertex]) {
+    let mut min = glam::Vec3::splat(f32::MAX);
+    let mut max = glam::Vec3::splat(f32::MIN);
+
+    for vertex in vertex_data.iter() {
+        min = min.min(vertex.position.into());
+        max = max.max(vertex.position.into());
+    }
+
+    let extent = max - min;
+
+    let (u_axis, v_axis) = if extent.x <= extent.y && extent.x <= extent.z {
+        (2, 1)
+    } else if extent.y <= extent.x && extent.y <= extent.z {
+        (0, 2)
+    } else {
+        (0, 1)
+    };
+
+    let project = |position: [f32; 3], axis: usize| {
+        if extent[axis] > 0.0 {
+            (position[axis] - min[axis]) / extent[axis]
+        } else {
+            0.0
+        }
+    };
+
+    for vertex in vertex_data.iter_mut() {
+        vertex.uv = [
+            project(vertex.position, u_axis),
+            project(vertex.position, v_axis),
+        ];
+    }
+}
+
+pub fn triangle_strip_to_list(index_data: &[u32]) -> Vec<u32> {
+    let mut out_data = Vec::with_capacity(index_data.len().saturating_sub(2) * 3);
+
+    for (i, window) in index_data.windows(3).enumerate() {
+        if i % 2 == 0 {
+            out_data.extend_from_slice(&[window[0], window[1], window[2]]);
+        } else {
+            out_data.extend_from_slice(&[window[1], window[0], window[2]]);
+        }
+    }
+
+    out_data
+}
+
+pub fn triangle_fan_to_list(index_data: &[u32]) -> Vec<u32> {
+    let mut out_data = Vec::with_capacity(index_data.len().saturating_sub(2) * 3);
+
+    if let Some((&first, rest)) = index_data.split_first() {
+        for window in rest.windows(2) {
+            out_data.extend_from_slice(&[first, window[0], window[1]]);
+        }
+    }
+
+    out_data
+}
+
+pub fn line_strip_to_list(index_data: &[u32], closed: bool) -> Vec<u32> {
+    let mut out_data = Vec::with_capacity(index_data.len() * 2);
+
+    for window in index_data.windows(2) {
+        out_data.extend_from_slice(&[window[0], window[1]]);
+    }
+
+    if closed && index_data.len() > 2 {
+        out_data.extend_from_slice(&[index_data[index_data.len() - 1], index_data[0]]);
+    }
+
+    out_data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3]) -> Vertex {
+        Vertex {
+            position,
+            uv: [0.0, 0.0],
+            normal: [0.0, 0.0, 0.0],
+            uv1: [0.0, 0.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
+        }
+    }
+
+    fn assert_normal(vertex: &Vertex, expected: glam::Vec3) {
+        let normal = glam::Vec3::from(vertex.normal);
+
+        assert!(normal.abs_diff_eq(expected, 1e-5), "{:?}", normal);
+    }
+
+    #[test]
+    fn sequential_indices_count_up() {
+        assert_eq!(sequential_indices(4), vec![0, 1, 2, 3]);
+        assert!(sequential_indices(0).is_empty());
+    }
+
+    #[test]
+    fn flat_normals_split_vertices_and_have_unit_length() {
+        // a big and a small triangle sharing an edge, folded along it
+        let mut vertex_data = vec![
+            vertex([0.0, 0.0, 0.0]),
+            vertex([10.0, 0.0, 0.0]),
+            vertex([0.0, 10.0, 0.0]),
+            vertex([0.0, 0.0, 0.5]),
+        ];
+        let mut index_data = vec![0, 1, 2, 0, 3, 1];
+
+        flat_normals(&mut vertex_data, &mut index_data);
+
+        assert_eq!(index_data, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(vertex_data.len(), 6);
+
+        assert_eq!(vertex_data[3].position, [0.0, 0.0, 0.0]);
+        assert_eq!(vertex_data[5].position, [10.0, 0.0, 0.0]);
+
+        for vertex in &vertex_data[..3] {
+            assert_normal(vertex, glam::Vec3::unit_z());
+        }
+
+        for vertex in &vertex_data[3..] {
+            assert_normal(vertex, glam::Vec3::unit_y());
+        }
+    }
+
+    #[test]
+    fn smooth_normals_average_the_adjacent_faces() {
+        let mut vertex_data = vec![
+            vertex([0.0, 0.0, 0.0]),
+            vertex([1.0, 0.0, 0.0]),
+            vertex([0.0, 1.0, 0.0]),
+            vertex([0.0, 0.0, 1.0]),
+            // not part of any triangle
+            vertex([5.0, 5.0, 5.0]),
+        ];
+        let index_data = vec![0, 1, 2, 0, 3, 1];
+
+        smooth_normals(&mut vertex_data, &index_data);
+
+        let shared = glam::vec3(0.0, 1.0, 1.0).normalize();
+
+        assert_normal(&vertex_data[0], shared);
+        assert_normal(&vertex_data[1], shared);
+        assert_normal(&vertex_data[2], glam::Vec3::unit_z());
+        assert_normal(&vertex_data[3], glam::Vec3::unit_y());
+        assert_normal(&vertex_data[4], glam::Vec3::zero());
+    }
+
+    #[test]
+    fn planar_uvs_span_the_largest_extents() {
+        let mut vertex_data = vec![
+            vertex([-1.0, 2.0, 3.0]),
+            vertex([1.0, 2.0, 3.0]),
+            vertex([1.0, 6.0, 3.0]),
+        ];
+
+        planar_uvs(&mut vertex_data);
+
+        assert_eq!(vertex_data[0].uv, [0.0, 0.0]);
+        assert_eq!(vertex_data[1].uv, [1.0, 0.0]);
+        assert_eq!(vertex_data[2].uv, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn planar_uvs_of_degenerate_geometry_are_zero() {
+        let mut point = vec![vertex([1.0, 2.0, 3.0]), vertex([1.0, 2.0, 3.0])];
+
+        planar_uvs(&mut point);
+
+        assert!(point.iter().all(|vertex| vertex.uv == [0.0, 0.0]));
+
+        let mut line = vec![vertex([0.0, 0.0, 0.0]), vertex([2.0, 0.0, 0.0])];
+
+        planar_uvs(&mut line);
+
+        assert_eq!(line[0].uv, [0.0, 0.0]);
+        assert_eq!(line[1].uv, [1.0, 0.0]);
+    }
+
+    #[test]
+    fn triangle_strips_alternate_the_winding() {
+        assert_eq!(
+            triangle_strip_to_list(&[0, 1, 2, 3, 4]),
+            vec![0, 1, 2, 2, 1, 3, 2, 3, 4]
+        );
+        assert!(triangle_strip_to_list(&[0, 1]).is_empty());
+    }
+
+    #[test]
+    fn triangle_fans_share_the_first_vertex() {
+        assert_eq!(triangle_fan_to_list(&[0, 1, 2, 3]), vec![0, 1, 2, 0, 2, 3]);
+        assert!(triangle_fan_to_list(&[]).is_empty());
+        assert!(triangle_fan_to_list(&[0]).is_empty());
+        assert!(triangle_fan_to_list(&[0, 1]).is_empty());
+    }
+
+    #[test]
+    fn line_strips_become_one_segment_per_pair() {
+        assert_eq!(line_strip_to_list(&[0, 1, 2], false), vec![0, 1, 1, 2]);
+        assert_eq!(line_strip_to_list(&[0, 1, 2], true), vec![0, 1, 1, 2, 2, 0]);
+        // a loop of two vertices would draw the same segment twice
+        assert_eq!(line_strip_to_list(&[0, 1], true), vec![0, 1]);
+        assert!(line_strip_to_list(&[0], true).is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+use super::{
+    assets::Assets,
+    manager::ResourceManager,
+    map::Map,
+    scene::{MapInstance, Scene},
+    LoaderError,
+};
+use crate::gpu::Context;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Detects changed files by comparing modification times, no OS services involved.
+// Nothing ever changes in asset sources without modification times.
+#[derive(Debug)]
+pub struct FileWatcher {
+    assets: Assets,
+    files: HashMap<PathBuf, Option<SystemTime>>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(assets: Assets) -> Self {
+        Self {
+            assets,
+            files: HashMap::new(),
+            interval: POLL_INTERVAL,
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Files already watched keep their modification time, so changes since the last
+    // poll are still reported.
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+
+        if !self.files.contains_key(path) {
+            self.files
+                .insert(path.to_owned(), self.assets.modified(path));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+
+        for (path, last_modified) in self.files.iter_mut() {
+            let new_modified = self.assets.modified(path);
+
+            if new_modified != *last_modified {
+                *last_modified = new_modified;
+
+                changed.push(path.clone());
+            }
+        }
+
+        changed
+    }
+}
+
+// Keeps a map loaded into the scene and reloads it whenever the map file changes on
+// disk. When only prefabs change, just the map nodes instancing them are reloaded, so the
+// other entities of the map stay valid. Entities that are not part of the map are left
+// alone.
+pub struct MapReloader {
+    path: PathBuf,
+    map: Map,
+    instance: Option<MapInstance>,
+    // buffer and image files of each prefab of the map, once it is loaded
+    dependencies: Vec<Vec<PathBuf>>,
+    watcher: FileWatcher,
+}
+
+impl MapReloader {
+    pub fn load<P: AsRef<Path>>(
+        context: &Context,
+        resource_manager: &mut ResourceManager,
+        scene: &mut Scene,
+        path: P,
+    ) -> Result<Self, LoaderError> {
+        let assets = resource_manager.assets().clone();
+        let path = path.as_ref().to_owned();
+        let map = Map::load(&*assets, &path).map_err(LoaderError::Map)?;
+
+        let mut reloader = Self {
+            path,
+            map,
+            instance: None,
+            dependencies: Vec::new(),
+            watcher: FileWatcher::new(assets),
+        };
+
+        reloader.instance = Some(scene.load_map_async(context, resource_manager, &reloader.map)?);
+        reloader.watch();
+
+        Ok(reloader)
+    }
+
+    fn watch(&mut self) {
+        self.watcher.clear();
+        self.watcher.watch(&self.path);
+
+        for source in &self.map.prefabs {
+            self.watcher.watch(source.path());
+        }
+
+        self.dependencies = vec![Vec::new(); self.map.prefabs.len()];
+    }
+
+    // Also watches the buffers and images of prefabs which finished loading since the
+    // last call.
+    fn watch_dependencies(&mut self, resource_manager: &ResourceManager, scene: &Scene) {
+        let prefab_ids = match self
+            .instance
+            .as_ref()
+            .and_then(|instance| scene.map_prefab_ids(instance))
+        {
+            Some(prefab_ids) => prefab_ids,
+            None => return,
+        };
+
+        for (dependencies, &prefab_id) in self.dependencies.iter_mut().zip(prefab_ids) {
+            if let Some(prefab) = resource_manager.prefab(prefab_id) {
+                if *dependencies != prefab.dependencies {
+                    for path in &prefab.dependencies {
+                        self.watcher.watch(path);
+                    }
+
+                    *dependencies = prefab.dependencies.clone();
+                }
+            }
+        }
+    }
+
+    // Indices of the prefabs of the map read from one of the `changed` files, either the
+    // glTF file itself or one of its buffers or images.
+    fn changed_prefabs(&self, changed: &[PathBuf]) -> Vec<usize> {
+        let empty = Vec::new();
+
+        self.map
+            .prefabs
+            .iter()
+            .enumerate()
+            .filter(|&(prefab_index, source)| {
+                let dependencies = self.dependencies.get(prefab_index).unwrap_or(&empty);
+
+                changed
+                    .iter()
+                    .any(|path| path == Path::new(source.path()) || dependencies.contains(path))
+            })
+            .map(|(prefab_index, _)| prefab_index)
+            .collect()
+    }
+
+    // On errors the map stays as it was.
+    pub fn update(
+        &mut self,
+        context: &Context,
+        resource_manager: &mut ResourceManager,
+        scene: &mut Scene,
+    ) -> Result<(), LoaderError> {
+        self.watch_dependencies(resource_manager, scene);
+
+        let changed = self.watcher.poll();
+
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        let changed_prefabs = self.changed_prefabs(&changed);
+
+        for &prefab_index in &changed_prefabs {
+            resource_manager.evict_prefab(self.map.prefabs[prefab_index].path());
+        }
+
+        let mut map_error = None;
+
+        if changed.contains(&self.path) {
+            match Map::load(&**resource_manager.assets(), &self.path) {
+                Ok(map) => {
+                    // load the new instance before dropping the old one, so unchanged
+                    // prefabs are taken from the cache instead of being loaded again
+                    let instance = scene.load_map_async(context, resource_manager, &map)?;
+
+                    if let Some(old_instance) = self.instance.replace(instance) {
+                        scene.unload_map(resource_manager, old_instance);
+                    }
+
+                    self.map = map;
+                    self.watch();
+
+                    return Ok(());
+                }
+                // the old map stays, but still shows the prefabs evicted above anew
+                Err(err) => map_error = Some(LoaderError::Map(err)),
+            }
+        }
+
+        if let Some(instance) = &self.instance {
+            for prefab_index in changed_prefabs {
+                scene.reload_map_prefab(
+                    context,
+                    resource_manager,
+                    instance,
+                    prefab_index,
+                    &self.map.prefabs[prefab_index],
+                );
+            }
+        }
+
+        map_error.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::{assets::DirectorySource, map::PrefabSource, prefab::SceneSelector};
+
+    #[test]
+    fn file_watcher_reports_touched_and_removed_files() {
+        let root = std::env::temp_dir().join(format!("gemini-watcher-{}", std::process::id()));
+        let file = root.join("door.gltf");
+
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(&file, b"{}").unwrap();
+
+        let mut watcher = FileWatcher::new(Assets::new(DirectorySource::new(&root)));
+        watcher.interval = Duration::from_secs(0);
+        watcher.watch("door.gltf");
+
+        assert!(watcher.poll().is_empty());
+
+        // rewrite the file until the file system shows a new modification time
+        let modified = std::fs::metadata(&file).unwrap().modified().unwrap();
+
+        while std::fs::metadata(&file).unwrap().modified().unwrap() == modified {
+            std::thread::sleep(Duration::from_millis(10));
+            std::fs::write(&file, b"{ }").unwrap();
+        }
+
+        assert_eq!(watcher.poll(), vec![PathBuf::from("door.gltf")]);
+        assert!(watcher.poll().is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(watcher.poll(), vec![PathBuf::from("door.gltf")]);
+    }
+
+    #[test]
+    fn changed_files_map_to_the_prefabs_read_from_them() {
+        let map = Map {
+            prefabs: vec![
+                PrefabSource::Path(String::from("door.gltf")),
+                PrefabSource::Path(String::from("lamp.gltf")),
+                PrefabSource::Scene {
+                    path: String::from("door.gltf"),
+                    scene: SceneSelector::Name(String::from("open")),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let reloader = MapReloader {
+            path: PathBuf::from("maps/test.json"),
+            map,
+            instance: None,
+            dependencies: vec![
+                vec![PathBuf::from("door.bin")],
+                vec![PathBuf::from("lamp.png")],
+                Vec::new(),
+            ],
+            watcher: FileWatcher::new(Assets::default()),
+        };
+
+        assert_eq!(
+            reloader.changed_prefabs(&[PathBuf::from("door.gltf")]),
+            vec![0, 2]
+        );
+        assert_eq!(
+            reloader
+                .changed_prefabs(&[PathBuf::from("maps/test.json"), PathBuf::from("lamp.gltf")]),
+            vec![1]
+        );
+        assert_eq!(
+            reloader.changed_prefabs(&[PathBuf::from("lamp.png")]),
+            vec![1]
+        );
+        assert_eq!(
+            reloader.changed_prefabs(&[PathBuf::from("door.bin")]),
+            vec![0]
+        );
+        assert!(reloader
+            .changed_prefabs(&[PathBuf::from("maps/test.json")])
+            .is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+use super::{
+    assets::Assets,
+    model::{import_gltf, GltfData},
+    LoaderError,
+};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Arc, Mutex};
+
+// Number of threads decoding files, started with the first file.
+#[cfg(not(target_arch = "wasm32"))]
+const WORKER_COUNT: usize = 2;
+
+type LoadResult = (usize, String, Result<GltfData, LoaderError>);
+
+type Job = (Assets, usize, String);
+
+// Decodes glTF files (buffers and images included) on a few worker threads. There are
+// no threads on the web, so files are decoded right away there.
+pub struct BackgroundLoader {
+    sender: Sender<LoadResult>,
+    receiver: Receiver<LoadResult>,
+    #[cfg(not(target_arch = "wasm32"))]
+    jobs: Option<Sender<Job>>,
+}
+
+impl Default for BackgroundLoader {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+
+        Self {
+            sender,
+            receiver,
+            #[cfg(not(target_arch = "wasm32"))]
+            jobs: None,
+        }
+    }
+}
+
+fn load(sender: &Sender<LoadResult>, (assets, prefab_id, path): Job) {
+    let result = import_gltf(&*assets, &path);
+
+    // the receiver only goes away together with the resource manager
+    let _ = sender.send((prefab_id, path, result));
+}
+
+impl BackgroundLoader {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_workers(&self) -> Sender<Job> {
+        let (jobs, job_receiver) = channel::<Job>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        for _ in 0..WORKER_COUNT {
+            let sender = self.sender.clone();
+            let job_receiver = job_receiver.clone();
+
+            // workers stop once the loader and with it the job sender is dropped
+            std::thread::spawn(move || loop {
+                let job = match job_receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+
+                load(&sender, job);
+            });
+        }
+
+        jobs
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn(&mut self, assets: Assets, prefab_id: usize, path: &str) {
+        if self.jobs.is_none() {
+            self.jobs = Some(self.start_workers());
+        }
+
+        let job = (assets, prefab_id, path.to_owned());
+
+        // the workers only stop when the job sender is gone
+        let _ = self.jobs.as_ref().unwrap().send(job);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn spawn(&mut self, assets: Assets, prefab_id: usize, path: &str) {
+        load(&self.sender, (assets, prefab_id, path.to_owned()));
+    }
+
+    pub fn try_recv(&self) -> Option<LoadResult> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::assets::MemorySource;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn workers_go_through_more_files_than_there_are_workers() {
+        let mut source = MemorySource::new();
+        source.insert("broken.gltf", b"not a glTF file".to_vec());
+
+        let assets = Assets::new(source);
+        let mut loader = BackgroundLoader::default();
+
+        let file_count = 4 * WORKER_COUNT;
+
+        for prefab_id in 0..file_count {
+            loader.spawn(assets.clone(), prefab_id, "broken.gltf");
+        }
+
+        let mut prefab_ids = Vec::new();
+        let start = Instant::now();
+
+        while prefab_ids.len() < file_count && start.elapsed() < Duration::from_secs(10) {
+            match loader.try_recv() {
+                Some((prefab_id, path, result)) => {
+                    assert_eq!(path, "broken.gltf");
+                    assert!(result.is_err());
+
+                    prefab_ids.push(prefab_id);
+                }
+                None => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
+
+        prefab_ids.sort_unstable();
+
+        assert_eq!(prefab_ids, (0..file_count).collect::<Vec<_>>());
+    }
+}
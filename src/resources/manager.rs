@@ -1,9 +1,441 @@
-use crate::gpu::{Geometry, Pipeline, Texture};
+use super::{
+    assets::Assets,
+    loader::BackgroundLoader,
+    model::{load_gltf, load_gltf_data},
+    prefab::{Prefab, SceneSelector},
+    LoaderError,
+};
+use crate::{
+    components::{material::PbrMaterial, MeshComponent, MeshPrimitive},
+    gpu::{
+        create_material_bind_group, uniform::MATERIAL_TEXTURE_COUNT, Context, Geometry, Pipeline,
+        SamplerDesc, Shading, Texture,
+    },
+    shapes::generate_quad,
+};
 use slab::Slab;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use wgpu::{BindGroup, PrimitiveTopology, Sampler};
+
+#[derive(Debug, Clone)]
+pub enum PrefabState {
+    Loading,
+    Loaded(Rc<Prefab>),
+    Failed(Rc<LoaderError>),
+}
+
+struct PrefabEntry {
+    path: PathBuf,
+    scene: Option<SceneSelector>,
+    state: PrefabState,
+    ref_count: usize,
+}
+
+fn retain(ref_counts: &mut HashMap<usize, usize>, id: usize) {
+    *ref_counts.entry(id).or_insert(0) += 1;
+}
+
+// Returns true if this was the last reference.
+fn release(ref_counts: &mut HashMap<usize, usize>, id: usize) -> bool {
+    let ref_count = ref_counts.get_mut(&id).unwrap();
+
+    *ref_count -= 1;
+
+    if *ref_count == 0 {
+        ref_counts.remove(&id);
+
+        true
+    } else {
+        false
+    }
+}
+
+fn report_warnings(prefab: &Prefab) {
+    for warning in &prefab.warnings {
+        eprintln!("warning: {}", warning);
+    }
+}
 
 #[derive(Default)]
 pub struct ResourceManager {
     pub geometries: Slab<Geometry>,
     pub pipelines: Slab<Pipeline>,
     pub texture: Slab<Texture>,
+    pub samplers: Slab<Sampler>,
+    pub material_bind_groups: Slab<BindGroup>,
+    known_pipelines: HashMap<(Shading, PrimitiveTopology, bool), usize>,
+    known_samplers: HashMap<SamplerDesc, usize>,
+    known_material_bind_groups: HashMap<[Option<usize>; MATERIAL_TEXTURE_COUNT], usize>,
+    prefabs: Slab<PrefabEntry>,
+    known_prefabs: HashMap<(PathBuf, Option<SceneSelector>), usize>,
+    geometry_refs: HashMap<usize, usize>,
+    pipeline_refs: HashMap<usize, usize>,
+    texture_refs: HashMap<usize, usize>,
+    material_bind_group_refs: HashMap<usize, usize>,
+    loader: BackgroundLoader,
+    assets: Assets,
+    placeholder_mesh: Option<MeshComponent>,
+    // white texture bound in place of the textures a material doesn't have
+    dummy_texture: Option<usize>,
+}
+
+impl ResourceManager {
+    pub fn assets(&self) -> &Assets {
+        &self.assets
+    }
+
+    // Only affects what is loaded from now on.
+    pub fn set_assets(&mut self, assets: Assets) {
+        self.assets = assets;
+    }
+
+    pub fn pipeline_for(
+        &mut self,
+        context: &Context,
+        shading: Shading,
+        topology: PrimitiveTopology,
+        double_sided: bool,
+    ) -> usize {
+        let pipelines = &mut self.pipelines;
+
+        *self
+            .known_pipelines
+            .entry((shading, topology, double_sided))
+            .or_insert_with(|| {
+                pipelines.insert(Pipeline::new(
+                    &context.device,
+                    &context.uniform_layouts,
+                    context.format,
+                    shading,
+                    topology,
+                    double_sided,
+                ))
+            })
+    }
+
+    pub fn sampler_for_desc(&mut self, context: &Context, desc: SamplerDesc) -> usize {
+        let samplers = &mut self.samplers;
+
+        *self
+            .known_samplers
+            .entry(desc)
+            .or_insert_with(|| samplers.insert(desc.create_sampler(&context.device)))
+    }
+
+    fn dummy_texture(&mut self, context: &Context) -> usize {
+        if let Some(texture_id) = self.dummy_texture {
+            return texture_id;
+        }
+
+        let sampler_id = self.sampler_for_desc(context, SamplerDesc::default());
+
+        let texture_id = self.texture.insert(Texture::new(
+            &context.device,
+            &context.queue,
+            (1, 1),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            1,
+            &[255; 4],
+            sampler_id,
+        ));
+
+        // never released, like the placeholder mesh
+        retain(&mut self.texture_refs, texture_id);
+
+        self.dummy_texture = Some(texture_id);
+
+        texture_id
+    }
+
+    pub fn material_bind_group_for(&mut self, context: &Context, material: &PbrMaterial) -> usize {
+        let key = material
+            .texture_slots()
+            .map(|slot| slot.map(|texture_info| texture_info.texture_id));
+
+        if let Some(&material_bind_group_id) = self.known_material_bind_groups.get(&key) {
+            return material_bind_group_id;
+        }
+
+        let dummy_texture = self.dummy_texture(context);
+        let textures = &self.texture;
+        let samplers = &self.samplers;
+
+        let binding = |texture_id: Option<usize>| {
+            let texture = &textures[texture_id.unwrap_or(dummy_texture)];
+
+            (&texture.view, &samplers[texture.sampler_id])
+        };
+
+        let bind_group =
+            create_material_bind_group(&context.device, &context.uniform_layouts, key.map(binding));
+
+        let material_bind_group_id = self.material_bind_groups.insert(bind_group);
+
+        self.known_material_bind_groups
+            .insert(key, material_bind_group_id);
+
+        material_bind_group_id
+    }
+
+    // Loads a scene of the glTF file at `path` unless it is already loaded. Every call
+    // has to be paired with a call to `release_prefab`.
+    pub fn load_prefab(
+        &mut self,
+        context: &Context,
+        path: &str,
+        scene: Option<&SceneSelector>,
+    ) -> Result<usize, LoaderError> {
+        let key = (PathBuf::from(path), scene.cloned());
+
+        if let Some(&prefab_id) = self.known_prefabs.get(&key) {
+            self.prefabs[prefab_id].ref_count += 1;
+
+            return Ok(prefab_id);
+        }
+
+        let prefab = load_gltf(context, self, path, scene)?;
+
+        report_warnings(&prefab);
+
+        Ok(self.insert_prefab(path, scene, PrefabState::Loaded(Rc::new(prefab))))
+    }
+
+    // Caches a prefab as if it was loaded from `path`, with one reference to it.
+    pub fn insert_prefab(
+        &mut self,
+        path: &str,
+        scene: Option<&SceneSelector>,
+        state: PrefabState,
+    ) -> usize {
+        let key = (PathBuf::from(path), scene.cloned());
+
+        let prefab_id = self.prefabs.insert(PrefabEntry {
+            path: key.0.clone(),
+            scene: key.1.clone(),
+            state,
+            ref_count: 1,
+        });
+
+        self.known_prefabs.insert(key, prefab_id);
+
+        prefab_id
+    }
+
+    // Like `load_prefab`, but decodes the file on a worker thread. The prefab becomes
+    // available once `poll_prefabs` has uploaded it.
+    pub fn load_prefab_async(&mut self, path: &str, scene: Option<&SceneSelector>) -> usize {
+        let key = (PathBuf::from(path), scene.cloned());
+
+        if let Some(&prefab_id) = self.known_prefabs.get(&key) {
+            self.prefabs[prefab_id].ref_count += 1;
+
+            return prefab_id;
+        }
+
+        let prefab_id = self.insert_prefab(path, scene, PrefabState::Loading);
+
+        self.loader.spawn(self.assets.clone(), prefab_id, path);
+
+        prefab_id
+    }
+
+    // Uploads the prefabs decoded since the last call. Returns true if any finished
+    // loading, successfully or not.
+    pub fn poll_prefabs(&mut self, context: &Context) -> bool {
+        let mut any_finished = false;
+
+        while let Some((prefab_id, path, result)) = self.loader.try_recv() {
+            // the prefab might have been released while it was loading
+            let scene = match self.prefabs.get(prefab_id) {
+                Some(entry)
+                    if matches!(entry.state, PrefabState::Loading)
+                        && entry.path == Path::new(&path) =>
+                {
+                    entry.scene.clone()
+                }
+                _ => continue,
+            };
+
+            self.prefabs[prefab_id].state = match result
+                .and_then(|gltf_data| load_gltf_data(context, self, &gltf_data, scene.as_ref()))
+            {
+                Ok(prefab) => {
+                    report_warnings(&prefab);
+
+                    PrefabState::Loaded(Rc::new(prefab))
+                }
+                Err(err) => PrefabState::Failed(Rc::new(err)),
+            };
+
+            any_finished = true;
+        }
+
+        any_finished
+    }
+
+    // Makes the next `load_prefab` of `path` read the file again. Prefabs already
+    // loaded from it stay valid until they are released.
+    pub fn evict_prefab(&mut self, path: &str) {
+        self.known_prefabs
+            .retain(|(prefab_path, _), _| prefab_path != Path::new(path));
+    }
+
+    pub fn prefab_state(&self, prefab_id: usize) -> PrefabState {
+        self.prefabs[prefab_id].state.clone()
+    }
+
+    pub fn prefab(&self, prefab_id: usize) -> Option<Rc<Prefab>> {
+        match &self.prefabs[prefab_id].state {
+            PrefabState::Loaded(prefab) => Some(prefab.clone()),
+            _ => None,
+        }
+    }
+
+    // Stand-in for prefabs that are still loading, textured with the dummy texture.
+    pub fn placeholder_mesh(&mut self, context: &Context) -> MeshComponent {
+        if let Some(mesh) = &self.placeholder_mesh {
+            return mesh.clone();
+        }
+
+        let mut mesh = MeshComponent::new();
+
+        let material = PbrMaterial::default();
+
+        mesh.primitives.push(MeshPrimitive {
+            geometry_id: self.geometries.insert(generate_quad(&context.device)),
+            pipeline_id: self.pipeline_for(
+                context,
+                Shading::Unlit,
+                PrimitiveTopology::TriangleList,
+                false,
+            ),
+            material,
+            material_bind_group_id: self.material_bind_group_for(context, &material),
+        });
+
+        // never released, so the placeholder stays around
+        self.retain_mesh(&mesh);
+
+        self.placeholder_mesh = Some(mesh.clone());
+
+        mesh
+    }
+
+    // Unloads the prefab and releases its meshes once the last reference to it is gone.
+    pub fn release_prefab(&mut self, prefab_id: usize) {
+        let entry = &mut self.prefabs[prefab_id];
+
+        entry.ref_count -= 1;
+
+        if entry.ref_count > 0 {
+            return;
+        }
+
+        let entry = self.prefabs.remove(prefab_id);
+        let key = (entry.path, entry.scene);
+
+        if self.known_prefabs.get(&key) == Some(&prefab_id) {
+            self.known_prefabs.remove(&key);
+        }
+
+        if let PrefabState::Loaded(prefab) = entry.state {
+            for (_, mesh) in &prefab.scene.meshes {
+                self.release_mesh(mesh);
+            }
+        }
+    }
+
+    // Blends the morph targets of the primitive's geometry. A geometry shared with other
+    // meshes is copied first, so they keep their own weights. Nothing happens while the
+    // geometry is already blended with `weights`, so meshes which never change their
+    // weights keep sharing it.
+    pub fn set_morph_weights(
+        &mut self,
+        context: &Context,
+        primitive: &mut MeshPrimitive,
+        weights: &[f32],
+    ) {
+        let geometry = &self.geometries[primitive.geometry_id];
+
+        match &geometry.morph {
+            Some(morph) if morph.weights != weights => {}
+            _ => return,
+        }
+
+        if self.geometry_refs.get(&primitive.geometry_id).copied() > Some(1) {
+            let geometry_id = self
+                .geometries
+                .insert(geometry.duplicate(&context.device).unwrap());
+
+            retain(&mut self.geometry_refs, geometry_id);
+            release(&mut self.geometry_refs, primitive.geometry_id);
+
+            primitive.geometry_id = geometry_id;
+        }
+
+        self.geometries[primitive.geometry_id].set_morph_weights(&context.queue, weights);
+    }
+
+    // Number of references to the prefab, 0 once it is unloaded.
+    #[cfg(test)]
+    pub fn prefab_ref_count(&self, prefab_id: usize) -> usize {
+        self.prefabs
+            .get(prefab_id)
+            .map_or(0, |entry| entry.ref_count)
+    }
+
+    #[cfg(test)]
+    pub fn geometry_ref_count(&self, geometry_id: usize) -> usize {
+        self.geometry_refs.get(&geometry_id).copied().unwrap_or(0)
+    }
+
+    pub fn retain_mesh(&mut self, mesh: &MeshComponent) {
+        for primitive in &mesh.primitives {
+            retain(&mut self.geometry_refs, primitive.geometry_id);
+            retain(&mut self.pipeline_refs, primitive.pipeline_id);
+            retain(
+                &mut self.material_bind_group_refs,
+                primitive.material_bind_group_id,
+            );
+
+            for texture_info in primitive.material.textures() {
+                retain(&mut self.texture_refs, texture_info.texture_id);
+            }
+        }
+    }
+
+    // Frees the GPU resources no other mesh is referencing anymore.
+    pub fn release_mesh(&mut self, mesh: &MeshComponent) {
+        for primitive in &mesh.primitives {
+            if release(&mut self.geometry_refs, primitive.geometry_id) {
+                self.geometries.remove(primitive.geometry_id);
+            }
+
+            if release(&mut self.pipeline_refs, primitive.pipeline_id) {
+                self.pipelines.remove(primitive.pipeline_id);
+
+                self.known_pipelines
+                    .retain(|_, pipeline_id| *pipeline_id != primitive.pipeline_id);
+            }
+
+            let material_bind_group_id = primitive.material_bind_group_id;
+
+            if release(&mut self.material_bind_group_refs, material_bind_group_id) {
+                self.material_bind_groups.remove(material_bind_group_id);
+
+                self.known_material_bind_groups
+                    .retain(|_, id| *id != material_bind_group_id);
+            }
+
+            for texture_info in primitive.material.textures() {
+                if release(&mut self.texture_refs, texture_info.texture_id) {
+                    self.texture.remove(texture_info.texture_id);
+                }
+            }
+        }
+    }
 }
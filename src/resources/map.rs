@@ -1,7 +1,79 @@
+use super::{assets::AssetSource, prefab::SceneSelector};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const MAP_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum MapError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    UnsupportedVersion {
+        path: PathBuf,
+        version: u32,
+    },
+    InvalidPrefabId {
+        path: PathBuf,
+        node: String,
+        prefab_id: usize,
+        prefab_count: usize,
+    },
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MapError::Io { path, source } => {
+                write!(f, "{}: {}", path.display(), source)
+            }
+            MapError::Json { path, source } => {
+                write!(f, "{}: invalid map file: {}", path.display(), source)
+            }
+            MapError::UnsupportedVersion { path, version } => write!(
+                f,
+                "{}: unsupported map version {} (expected {})",
+                path.display(),
+                version,
+                MAP_VERSION
+            ),
+            MapError::InvalidPrefabId {
+                path,
+                node,
+                prefab_id,
+                prefab_count,
+            } => write!(
+                f,
+                "{}: node '{}' references prefab {} but only {} prefabs are declared",
+                path.display(),
+                node,
+                prefab_id,
+                prefab_count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MapError::Io { source, .. } => Some(source),
+            MapError::Json { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Node {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     pub prefab_id: Option<usize>,
     pub children: Vec<Node>,
     pub translation: glam::Vec3,
@@ -12,6 +84,7 @@ pub struct Node {
 impl Default for Node {
     fn default() -> Self {
         Self {
+            name: None,
             prefab_id: None,
             children: Vec::new(),
             translation: glam::Vec3::zero(),
@@ -21,8 +94,233 @@ impl Default for Node {
     }
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+// Either just the path of a glTF file, which instantiates its default scene, or an
+// object which also selects the scene: `{"path": "door.glb", "scene": "open"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PrefabSource {
+    Path(String),
+    Scene { path: String, scene: SceneSelector },
+}
+
+impl PrefabSource {
+    pub fn path(&self) -> &str {
+        match self {
+            PrefabSource::Path(path) => path,
+            PrefabSource::Scene { path, .. } => path,
+        }
+    }
+
+    pub fn scene(&self) -> Option<&SceneSelector> {
+        match self {
+            PrefabSource::Path(_) => None,
+            PrefabSource::Scene { scene, .. } => Some(scene),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Map {
-    pub prefabs: Vec<String>,
+    pub version: u32,
+    #[serde(default)]
+    pub prefabs: Vec<PrefabSource>,
+    #[serde(default)]
     pub root: Node,
+    // file the map was loaded from, empty for maps built in code
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl Default for Map {
+    fn default() -> Self {
+        Self {
+            version: MAP_VERSION,
+            prefabs: Vec::new(),
+            root: Node::default(),
+            path: PathBuf::new(),
+        }
+    }
+}
+
+impl Map {
+    pub fn load<P: AsRef<Path>>(assets: &dyn AssetSource, path: P) -> Result<Self, MapError> {
+        let path = path.as_ref();
+
+        let data = assets
+            .read(path)
+            .and_then(|data| {
+                String::from_utf8(data)
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+            })
+            .map_err(|source| MapError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+
+        let mut map: Map = serde_json::from_str(&data).map_err(|source| MapError::Json {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        map.validate(path)?;
+        map.path = path.to_owned();
+
+        Ok(map)
+    }
+
+    // Writes the map for level editing tools, the game itself only loads maps.
+    #[allow(dead_code)]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), MapError> {
+        let path = path.as_ref();
+
+        self.validate(path)?;
+
+        let data = serde_json::to_string_pretty(self).map_err(|source| MapError::Json {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        std::fs::write(path, data).map_err(|source| MapError::Io {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    // Checks the version and that every node references a declared prefab. `path` is
+    // only used for the errors.
+    pub fn validate(&self, path: &Path) -> Result<(), MapError> {
+        if self.version != MAP_VERSION {
+            return Err(MapError::UnsupportedVersion {
+                path: path.to_owned(),
+                version: self.version,
+            });
+        }
+
+        self.validate_node(path, &self.root, String::from("root"))
+    }
+
+    fn validate_node(&self, path: &Path, node: &Node, node_path: String) -> Result<(), MapError> {
+        if let Some(prefab_id) = node.prefab_id {
+            if prefab_id >= self.prefabs.len() {
+                return Err(MapError::InvalidPrefabId {
+                    path: path.to_owned(),
+                    node: node_path,
+                    prefab_id,
+                    prefab_count: self.prefabs.len(),
+                });
+            }
+        }
+
+        for (i, child) in node.children.iter().enumerate() {
+            self.validate_node(path, child, format!("{}/children[{}]", node_path, i))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::assets::{DirectorySource, MemorySource};
+
+    fn load(json: &str) -> Result<Map, MapError> {
+        let mut source = MemorySource::new();
+        source.insert("maps/test.json", json.as_bytes().to_vec());
+
+        Map::load(&source, "maps/test.json")
+    }
+
+    #[test]
+    fn maps_load_with_their_prefabs_and_nodes() {
+        let map = load(
+            r#"{
+                "version": 1,
+                "prefabs": ["door.gltf", {"path": "door.gltf", "scene": "open"}],
+                "root": {"children": [{"name": "door", "prefab_id": 1}]}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(map.path, PathBuf::from("maps/test.json"));
+        assert_eq!(
+            map.prefabs[0],
+            PrefabSource::Path(String::from("door.gltf"))
+        );
+        assert_eq!(
+            map.prefabs[1].scene(),
+            Some(&SceneSelector::Name(String::from("open")))
+        );
+        assert_eq!(map.root.children[0].name.as_deref(), Some("door"));
+        assert_eq!(map.root.children[0].prefab_id, Some(1));
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let err = load(r#"{"version": 2}"#).unwrap_err();
+
+        assert!(matches!(
+            err,
+            MapError::UnsupportedVersion { version: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn undeclared_prefabs_are_rejected() {
+        let err = load(
+            r#"{
+                "version": 1,
+                "prefabs": ["door.gltf"],
+                "root": {"children": [{"prefab_id": 0}, {"children": [{"prefab_id": 1}]}]}
+            }"#,
+        )
+        .unwrap_err();
+
+        match err {
+            MapError::InvalidPrefabId {
+                node,
+                prefab_id,
+                prefab_count,
+                ..
+            } => {
+                assert_eq!(node, "root/children[1]/children[0]");
+                assert_eq!(prefab_id, 1);
+                assert_eq!(prefab_count, 1);
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn saved_maps_load_again() {
+        let root = std::env::temp_dir().join(format!("gemini-map-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+
+        let map = Map {
+            prefabs: vec![PrefabSource::Path(String::from("door.gltf"))],
+            root: Node {
+                children: vec![Node {
+                    prefab_id: Some(0),
+                    translation: glam::vec3(1.0, 2.0, 3.0),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        map.save(root.join("test.json")).unwrap();
+
+        let loaded = Map::load(&DirectorySource::new(&root), "test.json").unwrap();
+
+        assert_eq!(loaded.prefabs, map.prefabs);
+        assert_eq!(loaded.root.children[0].prefab_id, Some(0));
+        assert_eq!(
+            loaded.root.children[0].translation,
+            glam::vec3(1.0, 2.0, 3.0)
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
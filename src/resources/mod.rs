@@ -1,16 +1,189 @@
+pub mod animation;
+pub mod assets;
+mod generate;
+pub mod hot_reload;
+mod loader;
 pub mod manager;
 pub mod map;
 pub mod model;
+mod pixels;
 pub mod prefab;
 pub mod scene;
 
-#[derive(Debug, Clone)]
-pub struct LoaderError;
+use crate::gpu::uniform::MAX_JOINTS;
+use map::MapError;
+use prefab::SceneSelector;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    Indices,
+    Positions,
+    TexCoords(u32),
+    Normals,
+}
+
+impl std::fmt::Display for Attribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Attribute::Indices => write!(f, "indices"),
+            Attribute::Positions => write!(f, "POSITION"),
+            Attribute::TexCoords(set) => write!(f, "TEXCOORD_{}", set),
+            Attribute::Normals => write!(f, "NORMAL"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoaderError {
+    Gltf {
+        path: PathBuf,
+        source: gltf::Error,
+    },
+    MissingAttribute {
+        path: PathBuf,
+        mesh: usize,
+        primitive: usize,
+        attribute: Attribute,
+    },
+    MissingScene {
+        path: PathBuf,
+        scene: SceneSelector,
+    },
+    Map(MapError),
+}
 
 impl std::fmt::Display for LoaderError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Failed to load resource")
+        match self {
+            LoaderError::Gltf { path, source } => {
+                write!(f, "{}: failed to import glTF: {}", path.display(), source)
+            }
+            LoaderError::MissingAttribute {
+                path,
+                mesh,
+                primitive,
+                attribute,
+            } => write!(
+                f,
+                "{}: mesh {} primitive {} has no {}",
+                path.display(),
+                mesh,
+                primitive,
+                attribute
+            ),
+            LoaderError::MissingScene { path, scene } => {
+                write!(f, "{}: has no scene {}", path.display(), scene)
+            }
+            LoaderError::Map(source) => source.fmt(f),
+        }
     }
 }
 
-impl std::error::Error for LoaderError {}
+// Problems a glTF file could be loaded despite of, returned with the prefab.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoaderWarning {
+    GeneratedAttribute {
+        path: PathBuf,
+        mesh: usize,
+        primitive: usize,
+        attribute: Attribute,
+        fallback: &'static str,
+    },
+    // the influences of those joints are dropped
+    JointsOutOfRange {
+        path: PathBuf,
+        mesh: usize,
+        primitive: usize,
+    },
+    // the skin is ignored
+    SkinOutsideOfScene {
+        path: PathBuf,
+        skin: usize,
+    },
+    // joints beyond MAX_JOINTS don't move the mesh
+    TooManyJoints {
+        path: PathBuf,
+        skin: usize,
+        joint_count: usize,
+    },
+    // the node gets no extras component
+    InvalidExtras {
+        path: PathBuf,
+        node: usize,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for LoaderWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoaderWarning::GeneratedAttribute {
+                path,
+                mesh,
+                primitive,
+                attribute,
+                fallback,
+            } => write!(
+                f,
+                "{}: mesh {} primitive {} has no {}, using {}",
+                path.display(),
+                mesh,
+                primitive,
+                attribute,
+                fallback
+            ),
+            LoaderWarning::JointsOutOfRange {
+                path,
+                mesh,
+                primitive,
+            } => write!(
+                f,
+                "{}: mesh {} primitive {} references joints beyond {}, ignoring them",
+                path.display(),
+                mesh,
+                primitive,
+                MAX_JOINTS
+            ),
+            LoaderWarning::SkinOutsideOfScene { path, skin } => write!(
+                f,
+                "{}: skin {} has joints outside of the scenes, ignoring it",
+                path.display(),
+                skin
+            ),
+            LoaderWarning::TooManyJoints {
+                path,
+                skin,
+                joint_count,
+            } => write!(
+                f,
+                "{}: skin {} has {} joints, only the first {} are used",
+                path.display(),
+                skin,
+                joint_count,
+                MAX_JOINTS
+            ),
+            LoaderWarning::InvalidExtras {
+                path,
+                node,
+                message,
+            } => write!(
+                f,
+                "{}: node {} has invalid extras: {}",
+                path.display(),
+                node,
+                message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoaderError::Gltf { source, .. } => Some(source),
+            LoaderError::Map(source) => source.source(),
+            _ => None,
+        }
+    }
+}
@@ -1,162 +1,641 @@
-use super::{manager::ResourceManager, prefab::Prefab, scene::Scene, LoaderError};
+use super::{
+    animation::{AnimationClip, Channel, Interpolation, Keyframes},
+    assets::{normalize_path, AssetSource},
+    generate,
+    manager::ResourceManager,
+    pixels,
+    prefab::{Prefab, SceneSelector},
+    scene::Scene,
+    Attribute, LoaderError, LoaderWarning,
+};
 use crate::{
-    components::{material::PbrMaterial, MeshComponent, MeshPrimitive, TransformComponent},
-    gpu::{Context, Geometry, Texture, Vertex},
+    components::{
+        material::{AlphaMode, PbrMaterial, TextureInfo},
+        AnimationComponent, CameraComponent, ExtrasComponent, LightComponent, LightKind,
+        MeshComponent, MeshPrimitive, NameComponent, Pose, SkinComponent, TransformComponent,
+    },
+    gpu::{
+        mipmap, uniform::MAX_JOINTS, Context, Geometry, MorphTarget, SamplerDesc, Shading, Texture,
+        Vertex,
+    },
+};
+use gltf::{
+    animation::util::ReadOutputs,
+    buffer, image,
+    mesh::Mode,
+    texture::{MagFilter, MinFilter, WrappingMode},
+    Document, Node, Primitive,
 };
-use gltf::{buffer, image, Node, Primitive};
-use itertools::izip;
 use slotmap::DefaultKey;
-use std::collections::{hash_map::Entry, HashMap};
-use wgpu::TextureFormat;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use wgpu::{AddressMode, FilterMode, PrimitiveTopology};
+use winit::dpi::PhysicalSize;
 
+// Decoded contents of a glTF file, which can be produced off the main thread.
 #[derive(Debug)]
-struct GltfData {
+pub struct GltfData {
+    path: PathBuf,
+    document: Document,
     buffers: Vec<buffer::Data>,
     images: Vec<image::Data>,
+    // external buffer and image files read along with the glTF file
+    dependencies: Vec<PathBuf>,
 }
 
 #[derive(Debug, Default)]
 struct CacheData {
     known_meshes: HashMap<usize, MeshComponent>,
-    known_textures: HashMap<usize, usize>,
+    known_textures: HashMap<(usize, bool), usize>,
+    node_entities: HashMap<usize, DefaultKey>,
+    skinned_entities: Vec<(DefaultKey, usize)>,
+    warnings: Vec<LoaderWarning>,
 }
 
-fn load_primitive_geometry(
-    context: &Context,
-    buffers: &[gltf::buffer::Data],
+// Drops the influences of joints beyond MAX_JOINTS, which the shaders have no matrix
+// for, and renormalizes the remaining weights. A vertex left without influences isn't
+// skinned at all. Returns true if anything was dropped.
+fn limit_joint_influences(vertex: &mut Vertex) -> bool {
+    let mut limited = false;
+
+    for (joint, weight) in vertex.joints.iter_mut().zip(vertex.weights.iter_mut()) {
+        if *joint as usize >= MAX_JOINTS {
+            limited |= *weight != 0.0;
+
+            *joint = 0;
+            *weight = 0.0;
+        }
+    }
+
+    let sum: f32 = vertex.weights.iter().sum();
+
+    if limited && sum > 0.0 {
+        for weight in vertex.weights.iter_mut() {
+            *weight /= sum;
+        }
+    }
+
+    limited
+}
+
+// Vertices, triangle or line list indices and morph targets of a primitive, with the
+// attributes it lacks generated.
+struct PrimitiveData {
+    vertex_data: Vec<Vertex>,
+    index_data: Vec<u32>,
+    morph_targets: Vec<MorphTarget>,
+}
+
+fn read_primitive(
+    gltf_data: &GltfData,
+    mesh_index: usize,
     primitive: &Primitive,
-) -> Result<Geometry, Box<dyn std::error::Error>> {
+    warnings: &mut Vec<LoaderWarning>,
+) -> Result<PrimitiveData, LoaderError> {
     let mut index_data: Vec<u32> = Vec::new();
     let mut vertex_data: Vec<Vertex> = Vec::new();
 
-    let reader = primitive.reader(|b| Some(&buffers[b.index()]));
+    let reader = primitive.reader(|b| Some(&gltf_data.buffers[b.index()]));
 
-    let index_data_iter = reader.read_indices().ok_or(LoaderError)?;
-    let vertex_data_iter = reader.read_positions().ok_or(LoaderError)?;
-    let uv_data_iter = reader.read_tex_coords(0).ok_or(LoaderError {})?;
-    let normal_data_iter = reader.read_normals().ok_or(LoaderError {})?;
+    let vertex_data_iter =
+        reader
+            .read_positions()
+            .ok_or_else(|| LoaderError::MissingAttribute {
+                path: gltf_data.path.clone(),
+                mesh: mesh_index,
+                primitive: primitive.index(),
+                attribute: Attribute::Positions,
+            })?;
 
-    for index in index_data_iter.into_u32() {
-        index_data.push(index);
+    for position in vertex_data_iter {
+        vertex_data.push(Vertex {
+            position,
+            uv: [0.0, 0.0],
+            normal: [0.0, 0.0, 0.0],
+            uv1: [0.0, 0.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
+        });
     }
 
-    for (position, uv, normal) in izip!(vertex_data_iter, uv_data_iter.into_f32(), normal_data_iter)
+    if let (Some(joint_data_iter), Some(weight_data_iter)) =
+        (reader.read_joints(0), reader.read_weights(0))
     {
-        vertex_data.push(Vertex {
-            position,
-            uv,
-            normal,
+        for ((vertex, joints), weights) in vertex_data
+            .iter_mut()
+            .zip(joint_data_iter.into_u16())
+            .zip(weight_data_iter.into_f32())
+        {
+            vertex.joints = [
+                joints[0] as u32,
+                joints[1] as u32,
+                joints[2] as u32,
+                joints[3] as u32,
+            ];
+            vertex.weights = weights;
+        }
+
+        let mut limited = false;
+
+        for vertex in vertex_data.iter_mut() {
+            limited |= limit_joint_influences(vertex);
+        }
+
+        if limited {
+            warnings.push(LoaderWarning::JointsOutOfRange {
+                path: gltf_data.path.clone(),
+                mesh: mesh_index,
+                primitive: primitive.index(),
+            });
+        }
+    }
+
+    let mut report = |attribute, fallback| {
+        warnings.push(LoaderWarning::GeneratedAttribute {
+            path: gltf_data.path.clone(),
+            mesh: mesh_index,
+            primitive: primitive.index(),
+            attribute,
+            fallback,
         });
+    };
+
+    let indexed = if let Some(index_data_iter) = reader.read_indices() {
+        for index in index_data_iter.into_u32() {
+            index_data.push(index);
+        }
+
+        true
+    } else {
+        report(Attribute::Indices, "sequential indices");
+
+        index_data = generate::sequential_indices(vertex_data.len());
+
+        false
+    };
+
+    index_data = match primitive.mode() {
+        Mode::TriangleStrip => generate::triangle_strip_to_list(&index_data),
+        Mode::TriangleFan => generate::triangle_fan_to_list(&index_data),
+        Mode::LineStrip => generate::line_strip_to_list(&index_data, false),
+        Mode::LineLoop => generate::line_strip_to_list(&index_data, true),
+        Mode::Triangles | Mode::Lines | Mode::Points => index_data,
+    };
+
+    if let Some(uv_data_iter) = reader.read_tex_coords(0) {
+        for (vertex, uv) in vertex_data.iter_mut().zip(uv_data_iter.into_f32()) {
+            vertex.uv = uv;
+        }
+    } else {
+        report(Attribute::TexCoords(0), "planar uvs");
+
+        generate::planar_uvs(&mut vertex_data);
     }
 
-    Ok(Geometry::new(&context.device, &vertex_data, &index_data))
-}
+    if let Some(uv_data_iter) = reader.read_tex_coords(1) {
+        for (vertex, uv) in vertex_data.iter_mut().zip(uv_data_iter.into_f32()) {
+            vertex.uv1 = uv;
+        }
+    } else {
+        for vertex in vertex_data.iter_mut() {
+            vertex.uv1 = vertex.uv;
+        }
+    }
+
+    let mut morph_targets: Vec<MorphTarget> = reader
+        .read_morph_targets()
+        .map(|(positions, normals, _)| MorphTarget {
+            positions: positions.map_or_else(Vec::new, Iterator::collect),
+            normals: normals.map_or_else(Vec::new, Iterator::collect),
+        })
+        .collect();
+
+    if let Some(normal_data_iter) = reader.read_normals() {
+        for (vertex, normal) in vertex_data.iter_mut().zip(normal_data_iter) {
+            vertex.normal = normal;
+        }
+    } else if primitive_topology(primitive.mode()) == PrimitiveTopology::TriangleList {
+        if indexed {
+            report(Attribute::Normals, "smooth normals");
+
+            generate::smooth_normals(&mut vertex_data, &index_data);
+        } else {
+            report(Attribute::Normals, "flat normals");
+
+            let welded_index_data = index_data.clone();
+
+            generate::flat_normals(&mut vertex_data, &mut index_data);
+
+            // the morph targets have to follow the split up vertices
+            for target in &mut morph_targets {
+                if !target.positions.is_empty() {
+                    target.positions = welded_index_data
+                        .iter()
+                        .map(|&index| target.positions[index as usize])
+                        .collect();
+                }
 
-fn extend_data_color(data: &[u8], from_size: usize, to_size: usize) -> Vec<u8> {
-    assert!(to_size > from_size);
+                if !target.normals.is_empty() {
+                    target.normals = welded_index_data
+                        .iter()
+                        .map(|&index| target.normals[index as usize])
+                        .collect();
+                }
+            }
+        }
+    }
 
-    let padding_values = vec![255; to_size - from_size];
+    Ok(PrimitiveData {
+        vertex_data,
+        index_data,
+        morph_targets,
+    })
+}
 
-    let mut out_data = Vec::with_capacity((data.len() / from_size) * to_size);
+fn load_primitive_geometry(
+    context: &Context,
+    gltf_data: &GltfData,
+    mesh_index: usize,
+    primitive: &Primitive,
+    warnings: &mut Vec<LoaderWarning>,
+) -> Result<Geometry, LoaderError> {
+    let data = read_primitive(gltf_data, mesh_index, primitive, warnings)?;
 
-    for chunk in data.chunks(from_size) {
-        out_data.extend_from_slice(chunk);
+    if data.morph_targets.is_empty() {
+        Ok(Geometry::new(
+            &context.device,
+            &data.vertex_data,
+            &data.index_data,
+        ))
+    } else {
+        Ok(Geometry::with_morph_targets(
+            &context.device,
+            data.vertex_data,
+            data.index_data,
+            data.morph_targets,
+        ))
+    }
+}
 
-        out_data.extend(&padding_values);
+fn primitive_topology(mode: Mode) -> PrimitiveTopology {
+    match mode {
+        Mode::Points => PrimitiveTopology::PointList,
+        Mode::Lines | Mode::LineStrip | Mode::LineLoop => PrimitiveTopology::LineList,
+        Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan => {
+            PrimitiveTopology::TriangleList
+        }
     }
+}
 
-    out_data
+fn address_mode(wrapping_mode: WrappingMode) -> AddressMode {
+    match wrapping_mode {
+        WrappingMode::ClampToEdge => AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => AddressMode::Repeat,
+    }
+}
+
+fn sampler_desc(sampler: &gltf::texture::Sampler) -> SamplerDesc {
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => FilterMode::Nearest,
+        Some(MagFilter::Linear) | None => FilterMode::Linear,
+    };
+
+    let (min_filter, mipmap_filter, mipmaps) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) => (FilterMode::Nearest, FilterMode::Nearest, false),
+        Some(MinFilter::NearestMipmapNearest) => (FilterMode::Nearest, FilterMode::Nearest, true),
+        Some(MinFilter::NearestMipmapLinear) => (FilterMode::Nearest, FilterMode::Linear, true),
+        Some(MinFilter::Linear) => (FilterMode::Linear, FilterMode::Nearest, false),
+        Some(MinFilter::LinearMipmapNearest) => (FilterMode::Linear, FilterMode::Nearest, true),
+        Some(MinFilter::LinearMipmapLinear) | None => {
+            (FilterMode::Linear, FilterMode::Linear, true)
+        }
+    };
+
+    SamplerDesc {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+        mipmaps,
+    }
 }
 
 fn load_primitive_textures(
     context: &Context,
     images: &[gltf::image::Data],
     source_index: usize,
+    srgb: bool,
+    sampler_id: usize,
 ) -> Texture {
     let image_data = &images[source_index];
 
-    let copy_buffer;
-
-    let (pixels, format) = match image_data.format {
-        gltf::image::Format::R8 => (&image_data.pixels, TextureFormat::R8Unorm),
-        gltf::image::Format::R8G8 => (&image_data.pixels, TextureFormat::Rg8Unorm),
-        gltf::image::Format::R8G8B8 => (
-            {
-                copy_buffer = extend_data_color(&image_data.pixels, 3, 4);
-                &copy_buffer
-            },
-            TextureFormat::Rgba8Unorm,
-        ),
-        gltf::image::Format::R8G8B8A8 => (&image_data.pixels, TextureFormat::Rgba8Unorm),
-        gltf::image::Format::B8G8R8 => (
-            {
-                copy_buffer = extend_data_color(&image_data.pixels, 3, 4);
-                &copy_buffer
-            },
-            TextureFormat::Bgra8Unorm,
-        ),
-        gltf::image::Format::B8G8R8A8 => (&image_data.pixels, TextureFormat::Bgra8Unorm),
-        gltf::image::Format::R16 => (&image_data.pixels, TextureFormat::R16Uint),
-        gltf::image::Format::R16G16 => (&image_data.pixels, TextureFormat::Rg16Uint),
-        gltf::image::Format::R16G16B16 => (
-            {
-                copy_buffer = extend_data_color(&image_data.pixels, 6, 8);
-                &copy_buffer
-            },
-            TextureFormat::Rgba16Uint,
-        ),
-        gltf::image::Format::R16G16B16A16 => (&image_data.pixels, TextureFormat::Rgba16Uint),
-    };
+    let (pixels, format) = pixels::texture_data(image_data, srgb);
 
-    Texture::new(
-        &context.device,
-        &context.queue,
-        &context.uniform_layouts,
-        (image_data.width, image_data.height),
-        format,
-        &pixels,
-    )
+    let size = (image_data.width, image_data.height);
+
+    if let Some((channels, srgb)) = mipmap::mipmap_layout(format) {
+        Texture::new(
+            &context.device,
+            &context.queue,
+            size,
+            format,
+            mipmap::mip_level_count(size),
+            &mipmap::generate_mipmaps(size, channels, srgb, &pixels),
+            sampler_id,
+        )
+    } else {
+        Texture::new(
+            &context.device,
+            &context.queue,
+            size,
+            format,
+            1,
+            &pixels,
+            sampler_id,
+        )
+    }
 }
 
 fn load_primtive(
     context: &Context,
     resource_manager: &mut ResourceManager,
     gltf_data: &GltfData,
+    mesh_index: usize,
     gltf_primitive: &Primitive,
-    known_textures: &mut HashMap<usize, usize>,
-) -> Result<MeshPrimitive, Box<dyn std::error::Error>> {
+    known_textures: &mut HashMap<(usize, bool), usize>,
+    warnings: &mut Vec<LoaderWarning>,
+) -> Result<MeshPrimitive, LoaderError> {
     let geometry_id = resource_manager.geometries.insert(load_primitive_geometry(
         context,
-        &gltf_data.buffers,
-        &gltf_primitive,
+        gltf_data,
+        mesh_index,
+        gltf_primitive,
+        warnings,
     )?);
 
-    let gltf_pbr_material = gltf_primitive.material().pbr_metallic_roughness();
+    let gltf_material = gltf_primitive.material();
+    let gltf_pbr_material = gltf_material.pbr_metallic_roughness();
 
-    let color_texture = if let Some(info) = gltf_pbr_material.base_color_texture() {
-        let source_index = info.texture().source().index();
+    let mut load_texture = |texture: gltf::Texture, tex_coord: u32, srgb: bool| {
+        let key = (texture.index(), srgb);
 
-        Some(*known_textures.entry(source_index).or_insert_with(|| {
-            let texture = load_primitive_textures(context, &gltf_data.images, source_index);
+        let texture_id = *known_textures.entry(key).or_insert_with(|| {
+            let sampler_id =
+                resource_manager.sampler_for_desc(context, sampler_desc(&texture.sampler()));
+
+            let texture = load_primitive_textures(
+                context,
+                &gltf_data.images,
+                texture.source().index(),
+                srgb,
+                sampler_id,
+            );
 
             resource_manager.texture.insert(texture)
-        }))
+        });
+
+        TextureInfo {
+            texture_id,
+            tex_coord,
+        }
+    };
+
+    let (alpha_mode, alpha_cutoff) = match gltf_material.alpha_mode() {
+        gltf::material::AlphaMode::Opaque => (AlphaMode::Opaque, 0.5),
+        gltf::material::AlphaMode::Mask => (AlphaMode::Mask, gltf_material.alpha_cutoff()),
+        gltf::material::AlphaMode::Blend => (AlphaMode::Blend, 0.5),
+    };
+
+    // materials are lit unless they ask not to be
+    let shading = if gltf_material.unlit() {
+        Shading::Unlit
     } else {
-        None
+        Shading::Lit
     };
 
     let material = PbrMaterial {
         color: gltf_pbr_material.base_color_factor().into(),
-        color_texture,
+        color_texture: gltf_pbr_material
+            .base_color_texture()
+            .map(|info| load_texture(info.texture(), info.tex_coord(), true)),
+        metallic: gltf_pbr_material.metallic_factor(),
+        roughness: gltf_pbr_material.roughness_factor(),
+        metallic_roughness_texture: gltf_pbr_material
+            .metallic_roughness_texture()
+            .map(|info| load_texture(info.texture(), info.tex_coord(), false)),
+        normal_texture: gltf_material
+            .normal_texture()
+            .map(|normal| load_texture(normal.texture(), normal.tex_coord(), false)),
+        normal_scale: gltf_material
+            .normal_texture()
+            .map_or(1.0, |normal| normal.scale()),
+        occlusion_texture: gltf_material
+            .occlusion_texture()
+            .map(|occlusion| load_texture(occlusion.texture(), occlusion.tex_coord(), false)),
+        occlusion_strength: gltf_material
+            .occlusion_texture()
+            .map_or(1.0, |occlusion| occlusion.strength()),
+        emissive: gltf_material.emissive_factor().into(),
+        emissive_texture: gltf_material
+            .emissive_texture()
+            .map(|info| load_texture(info.texture(), info.tex_coord(), true)),
+        alpha_mode,
+        alpha_cutoff,
+        double_sided: gltf_material.double_sided(),
     };
 
     Ok(MeshPrimitive {
         geometry_id,
-        pipeline_id: 0,
+        pipeline_id: resource_manager.pipeline_for(
+            context,
+            shading,
+            primitive_topology(gltf_primitive.mode()),
+            material.double_sided,
+        ),
         material,
+        material_bind_group_id: resource_manager.material_bind_group_for(context, &material),
+    })
+}
+
+// Perspective cameras without an aspect ratio take the one of `size`, the size of the
+// render target.
+fn load_camera(camera: &gltf::Camera, size: PhysicalSize<u32>) -> CameraComponent {
+    match camera.projection() {
+        gltf::camera::Projection::Perspective(perspective) => CameraComponent::new(
+            perspective.yfov(),
+            perspective
+                .aspect_ratio()
+                .unwrap_or(size.width as f32 / size.height as f32),
+            perspective.znear(),
+            perspective.zfar().unwrap_or(f32::INFINITY),
+        ),
+        gltf::camera::Projection::Orthographic(orthographic) => CameraComponent::orthographic(
+            orthographic.ymag(),
+            orthographic.xmag() / orthographic.ymag(),
+            orthographic.znear(),
+            orthographic.zfar(),
+        ),
+    }
+}
+
+fn load_light(light: &gltf::khr_lights_punctual::Light) -> LightComponent {
+    let kind = match light.kind() {
+        gltf::khr_lights_punctual::Kind::Directional => LightKind::Directional,
+        gltf::khr_lights_punctual::Kind::Point => LightKind::Point,
+        gltf::khr_lights_punctual::Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => LightKind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        },
+    };
+
+    LightComponent {
+        kind,
+        color: light.color().into(),
+        intensity: light.intensity(),
+        range: light.range(),
+        ..Default::default()
+    }
+}
+
+fn load_skin(
+    gltf_data: &GltfData,
+    skin: &gltf::Skin,
+    node_entities: &HashMap<usize, DefaultKey>,
+    warnings: &mut Vec<LoaderWarning>,
+) -> Option<SkinComponent> {
+    let joints: Option<Vec<DefaultKey>> = skin
+        .joints()
+        .map(|joint| node_entities.get(&joint.index()).copied())
+        .collect();
+
+    let joints = match joints {
+        Some(joints) => joints,
+        None => {
+            warnings.push(LoaderWarning::SkinOutsideOfScene {
+                path: gltf_data.path.clone(),
+                skin: skin.index(),
+            });
+
+            return None;
+        }
+    };
+
+    if joints.len() > MAX_JOINTS {
+        warnings.push(LoaderWarning::TooManyJoints {
+            path: gltf_data.path.clone(),
+            skin: skin.index(),
+            joint_count: joints.len(),
+        });
+    }
+
+    let reader = skin.reader(|b| Some(&gltf_data.buffers[b.index()]));
+
+    let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+        Some(matrices) => matrices
+            .map(|matrix| glam::Mat4::from_cols_array_2d(&matrix))
+            .collect(),
+        None => vec![glam::Mat4::identity(); joints.len()],
+    };
+
+    Some(SkinComponent {
+        joints,
+        inverse_bind_matrices,
     })
 }
 
+fn load_animation(
+    gltf_data: &GltfData,
+    animation: &gltf::Animation,
+    node_entities: &HashMap<usize, DefaultKey>,
+    targets: &mut Vec<DefaultKey>,
+) -> AnimationClip {
+    let mut clip = AnimationClip {
+        name: animation.name().map(String::from),
+        ..Default::default()
+    };
+
+    for channel in animation.channels() {
+        let entity = match node_entities.get(&channel.target().node().index()) {
+            Some(&entity) => entity,
+            None => continue,
+        };
+
+        let reader = channel.reader(|b| Some(&gltf_data.buffers[b.index()]));
+
+        let (times, outputs) = match (reader.read_inputs(), reader.read_outputs()) {
+            (Some(times), Some(outputs)) => (times.collect::<Vec<f32>>(), outputs),
+            _ => continue,
+        };
+
+        let keyframes = match outputs {
+            ReadOutputs::Translations(values) => {
+                Keyframes::Translation(values.map(glam::Vec3::from).collect())
+            }
+            ReadOutputs::Rotations(values) => Keyframes::Rotation(
+                values
+                    .into_f32()
+                    .map(|[x, y, z, w]| glam::Quat::from_xyzw(x, y, z, w))
+                    .collect(),
+            ),
+            ReadOutputs::Scales(values) => Keyframes::Scale(values.map(glam::Vec3::from).collect()),
+            ReadOutputs::MorphTargetWeights(values) => {
+                let values: Vec<f32> = values.into_f32().collect();
+                let keyframe_count = match channel.sampler().interpolation() {
+                    gltf::animation::Interpolation::CubicSpline => times.len() * 3,
+                    _ => times.len(),
+                };
+
+                if keyframe_count == 0 {
+                    continue;
+                }
+
+                Keyframes::Weights {
+                    count: values.len() / keyframe_count,
+                    values,
+                }
+            }
+        };
+
+        if times.is_empty() {
+            continue;
+        }
+
+        let target = match targets.iter().position(|&target| target == entity) {
+            Some(target) => target,
+            None => {
+                targets.push(entity);
+
+                targets.len() - 1
+            }
+        };
+
+        let interpolation = match channel.sampler().interpolation() {
+            gltf::animation::Interpolation::Linear => Interpolation::Linear,
+            gltf::animation::Interpolation::Step => Interpolation::Step,
+            gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+        };
+
+        clip.duration = clip.duration.max(times[times.len() - 1]);
+
+        clip.channels.push(Channel {
+            target,
+            interpolation,
+            times,
+            keyframes,
+        });
+    }
+
+    clip
+}
+
 fn load_node(
     context: &Context,
     resource_manager: &mut ResourceManager,
@@ -165,7 +644,7 @@ fn load_node(
     gltf_data: &GltfData,
     parent: Option<DefaultKey>,
     cache: &mut CacheData,
-) -> Result<DefaultKey, Box<dyn std::error::Error>> {
+) -> Result<DefaultKey, LoaderError> {
     let gltf_transform = node.transform().decomposed();
     let transform = TransformComponent {
         translation: gltf_transform.0.into(),
@@ -176,8 +655,27 @@ fn load_node(
     };
 
     let entity = scene.create_entity(transform);
+    cache.node_entities.insert(node.index(), entity);
+
+    if let Some(name) = node.name() {
+        scene.names.insert(entity, NameComponent::new(name));
+    }
+
+    if let Some(extras) = node.extras() {
+        match serde_json::from_str(extras.get()) {
+            Ok(extras) => {
+                scene.extras.insert(entity, ExtrasComponent { extras });
+            }
+            Err(err) => cache.warnings.push(LoaderWarning::InvalidExtras {
+                path: gltf_data.path.clone(),
+                node: node.index(),
+                message: err.to_string(),
+            }),
+        }
+    }
+
     if let Some(mesh) = node.mesh() {
-        let mesh_component = match cache.known_meshes.entry(mesh.index()) {
+        let mut mesh_component = match cache.known_meshes.entry(mesh.index()) {
             Entry::Occupied(v) => v.get().clone(),
             Entry::Vacant(v) => {
                 let mut mc = MeshComponent::new();
@@ -187,18 +685,64 @@ fn load_node(
                         context,
                         resource_manager,
                         gltf_data,
+                        mesh.index(),
                         &gltf_primitive,
                         &mut cache.known_textures,
+                        &mut cache.warnings,
                     )?);
                 }
 
+                let target_count = mesh
+                    .primitives()
+                    .map(|gltf_primitive| gltf_primitive.morph_targets().count())
+                    .max()
+                    .unwrap_or(0);
+
+                mc.weights = mesh
+                    .weights()
+                    .map_or_else(|| vec![0.0; target_count], <[f32]>::to_vec);
+
                 v.insert(mc.clone());
 
                 mc
             }
         };
 
+        if let Some(weights) = node.weights() {
+            mesh_component.weights = weights.to_vec();
+        }
+
+        resource_manager.retain_mesh(&mesh_component);
+
         scene.meshes.insert(entity, mesh_component);
+
+        if let Some(skin) = node.skin() {
+            cache.skinned_entities.push((entity, skin.index()));
+        }
+    }
+
+    if let Some(camera) = node.camera() {
+        // glTF cameras look down -Z while ours look down +Z
+        let camera_entity = scene.create_entity(TransformComponent {
+            rotation: glam::Quat::from_rotation_y(std::f32::consts::PI),
+            parent: Some(entity),
+            ..Default::default()
+        });
+
+        scene
+            .cameras
+            .insert(camera_entity, load_camera(&camera, context.size()));
+    }
+
+    if let Some(light) = node.light() {
+        // same as for cameras, glTF lights shine along -Z
+        let light_entity = scene.create_entity(TransformComponent {
+            rotation: glam::Quat::from_rotation_y(std::f32::consts::PI),
+            parent: Some(entity),
+            ..Default::default()
+        });
+
+        scene.lights.insert(light_entity, load_light(&light));
     }
 
     for child_node in node.children() {
@@ -216,35 +760,469 @@ fn load_node(
     Ok(entity)
 }
 
-pub fn load_gltf(
-    context: &Context,
-    resource_manager: &mut ResourceManager,
-    path: &str,
-) -> Result<Prefab, Box<dyn std::error::Error>> {
-    let (document, buffers, images) = gltf::import(path)?;
-    assert_eq!(buffers.len(), document.buffers().count());
-    assert_eq!(images.len(), document.images().count());
+// Percent escapes are the only thing to decode in the relative URIs glTF files use.
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
 
-    let gltf_data = GltfData { buffers, images };
+    while i < bytes.len() {
+        let escaped = match (bytes[i], uri.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) => u8::from_str_radix(hex, 16).ok(),
+            _ => None,
+        };
 
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Path of the file a URI relative to the glTF file refers to, None for data URIs and
+// other schemes.
+fn uri_path(base: &Path, uri: &str) -> Option<PathBuf> {
+    if uri.contains(':') {
+        None
+    } else {
+        Some(normalize_path(&base.join(decode_uri(uri))))
+    }
+}
+
+// Reads base64 data URIs or files relative to the glTF file from the asset source.
+fn read_uri(assets: &dyn AssetSource, base: &Path, uri: &str) -> Result<Vec<u8>, gltf::Error> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data
+            .split_once(";base64,")
+            .ok_or(gltf::Error::UnsupportedScheme)?;
+
+        base64::decode(encoded).map_err(gltf::Error::Base64)
+    } else if let Some(path) = uri_path(base, uri) {
+        assets.read(&path).map_err(gltf::Error::Io)
+    } else {
+        Err(gltf::Error::UnsupportedScheme)
+    }
+}
+
+// Files of the buffers and images which are neither embedded nor data URIs.
+fn external_files(base: &Path, document: &Document) -> Vec<PathBuf> {
+    let buffer_uris = document
+        .buffers()
+        .filter_map(|buffer| match buffer.source() {
+            buffer::Source::Uri(uri) => Some(uri),
+            buffer::Source::Bin => None,
+        });
+
+    let image_uris = document.images().filter_map(|image| match image.source() {
+        image::Source::Uri { uri, .. } => Some(uri),
+        image::Source::View { .. } => None,
+    });
+
+    let mut files: Vec<PathBuf> = buffer_uris
+        .chain(image_uris)
+        .filter_map(|uri| uri_path(base, uri))
+        .collect();
+
+    files.sort();
+    files.dedup();
+
+    files
+}
+
+fn import_buffers(
+    assets: &dyn AssetSource,
+    base: &Path,
+    document: &Document,
+    mut blob: Option<Vec<u8>>,
+) -> Result<Vec<buffer::Data>, gltf::Error> {
+    let mut buffers = Vec::new();
+
+    for buffer in document.buffers() {
+        let mut data = match buffer.source() {
+            buffer::Source::Bin => blob.take().ok_or(gltf::Error::MissingBlob)?,
+            buffer::Source::Uri(uri) => read_uri(assets, base, uri)?,
+        };
+
+        if data.len() < buffer.length() {
+            return Err(gltf::Error::BufferLength {
+                buffer: buffer.index(),
+                expected: buffer.length(),
+                actual: data.len(),
+            });
+        }
+
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+
+        buffers.push(buffer::Data(data));
+    }
+
+    Ok(buffers)
+}
+
+fn import_images(
+    assets: &dyn AssetSource,
+    base: &Path,
+    document: &Document,
+    buffers: &[buffer::Data],
+) -> Result<Vec<image::Data>, gltf::Error> {
+    let mut images = Vec::new();
+
+    for image in document.images() {
+        let image_data = match image.source() {
+            image::Source::View { view, mime_type } => {
+                let start = view.offset();
+                let encoded = &buffers[view.buffer().index()][start..start + view.length()];
+
+                pixels::decode_image(encoded, Some(mime_type), None)?
+            }
+            image::Source::Uri { uri, mime_type } => {
+                let mime_type = mime_type.or_else(|| {
+                    uri.strip_prefix("data:")
+                        .and_then(|data| data.split(';').next())
+                });
+
+                let encoded = read_uri(assets, base, uri)?;
+
+                pixels::decode_image(&encoded, mime_type, Some(uri))?
+            }
+        };
+
+        images.push(image_data);
+    }
+
+    Ok(images)
+}
+
+// Reads .glb files as well as .gltf files with their buffers and images in separate
+// files or embedded as data URIs.
+pub fn import_gltf(assets: &dyn AssetSource, path: &str) -> Result<GltfData, LoaderError> {
+    let to_error = |source| LoaderError::Gltf {
+        path: PathBuf::from(path),
+        source,
+    };
+
+    let data = assets
+        .read(Path::new(path))
+        .map_err(|err| to_error(gltf::Error::Io(err)))?;
+
+    let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(&data).map_err(to_error)?;
+
+    let base = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+    let buffers = import_buffers(assets, base, &document, blob).map_err(to_error)?;
+    let images = import_images(assets, base, &document, &buffers).map_err(to_error)?;
+    let dependencies = external_files(base, &document);
+
+    Ok(GltfData {
+        path: PathBuf::from(path),
+        document,
+        buffers,
+        images,
+        dependencies,
+    })
+}
+
+// Without a selector the default scene is used, or the first one if there is none.
+fn find_scene<'a>(
+    gltf_data: &'a GltfData,
+    scene: Option<&SceneSelector>,
+) -> Result<Option<gltf::Scene<'a>>, LoaderError> {
+    let document = &gltf_data.document;
+
+    let found = match scene {
+        None => {
+            return Ok(document
+                .default_scene()
+                .or_else(|| document.scenes().next()))
+        }
+        Some(SceneSelector::Index(index)) => document.scenes().nth(*index),
+        Some(SceneSelector::Name(name)) => document
+            .scenes()
+            .find(|document_scene| document_scene.name() == Some(name.as_str())),
+    };
+
+    found.map(Some).ok_or_else(|| LoaderError::MissingScene {
+        path: gltf_data.path.clone(),
+        scene: scene.unwrap().clone(),
+    })
+}
+
+pub fn load_gltf_data(
+    context: &Context,
+    resource_manager: &mut ResourceManager,
+    gltf_data: &GltfData,
+    scene: Option<&SceneSelector>,
+) -> Result<Prefab, LoaderError> {
     let mut prefab = Prefab::default();
     prefab.root = prefab.scene.create_entity(TransformComponent::default());
 
     let mut cache = CacheData::default();
 
-    for document_scene in document.scenes() {
+    if let Some(document_scene) = find_scene(gltf_data, scene)? {
         for node in document_scene.nodes() {
             load_node(
                 context,
                 resource_manager,
                 &mut prefab.scene,
                 &node,
-                &gltf_data,
+                gltf_data,
                 Some(prefab.root),
                 &mut cache,
             )?;
         }
     }
 
+    for (entity, skin_index) in cache.skinned_entities {
+        let skin = gltf_data.document.skins().nth(skin_index).unwrap();
+
+        if let Some(skin_component) =
+            load_skin(gltf_data, &skin, &cache.node_entities, &mut cache.warnings)
+        {
+            prefab.scene.skins.insert(entity, skin_component);
+        }
+    }
+
+    let mut animation_component = AnimationComponent::default();
+
+    for animation in gltf_data.document.animations() {
+        let clip = load_animation(
+            gltf_data,
+            &animation,
+            &cache.node_entities,
+            &mut animation_component.targets,
+        );
+
+        animation_component.clips.push(Rc::new(clip));
+    }
+
+    if !animation_component.clips.is_empty() {
+        animation_component.bind_pose = animation_component
+            .targets
+            .iter()
+            .map(|&target| {
+                Pose::new(
+                    &prefab.scene.transforms[target],
+                    prefab.scene.meshes.get(target),
+                )
+            })
+            .collect();
+
+        // plays the first clip like most glTF viewers do
+        animation_component.play(0);
+
+        prefab
+            .scene
+            .animations
+            .insert(prefab.root, animation_component);
+    }
+
+    prefab.warnings = cache.warnings;
+    prefab.dependencies = gltf_data.dependencies.clone();
+
     Ok(prefab)
 }
+
+pub fn load_gltf(
+    context: &Context,
+    resource_manager: &mut ResourceManager,
+    path: &str,
+    scene: Option<&SceneSelector>,
+) -> Result<Prefab, LoaderError> {
+    let gltf_data = import_gltf(&**resource_manager.assets(), path)?;
+
+    load_gltf_data(context, resource_manager, &gltf_data, scene)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::assets::MemorySource;
+
+    fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+
+        ::image::png::PngEncoder::new(&mut encoded)
+            .encode(pixels, width, height, ::image::ColorType::Rgba8)
+            .unwrap();
+
+        encoded
+    }
+
+    #[test]
+    fn import_gltf_reads_external_and_embedded_data() {
+        let positions: Vec<u8> = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+
+        let indices: Vec<u8> = [0u16, 1, 2, 0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+
+        let png = encode_png(1, 1, &[255, 0, 0, 255]);
+
+        let json = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "buffers": [
+                    {{"uri": "data/triangle%20positions.bin", "byteLength": 36}},
+                    {{"uri": "data:application/octet-stream;base64,{}", "byteLength": 8}}
+                ],
+                "bufferViews": [
+                    {{"buffer": 0, "byteLength": 36}},
+                    {{"buffer": 1, "byteLength": 6}}
+                ],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [1, 1, 0]}},
+                    {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+                ],
+                "images": [{{"uri": "data:image/png;base64,{}"}}],
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "indices": 1}}]}}],
+                "nodes": [{{"mesh": 0}}],
+                "scenes": [{{"nodes": [0]}}]
+            }}"#,
+            base64::encode(&indices),
+            base64::encode(&png)
+        );
+
+        let mut source = MemorySource::new();
+        source.insert("models/triangle.gltf", json.into_bytes());
+        source.insert("models/data/triangle positions.bin", positions.clone());
+
+        let gltf_data = import_gltf(&source, "models/triangle.gltf").unwrap();
+
+        assert_eq!(
+            gltf_data.dependencies,
+            vec![PathBuf::from("models/data/triangle positions.bin")]
+        );
+        assert_eq!(gltf_data.buffers.len(), 2);
+        assert_eq!(&gltf_data.buffers[0][..36], &positions[..]);
+        assert_eq!(&gltf_data.buffers[1][..8], &indices[..]);
+
+        assert_eq!(gltf_data.images.len(), 1);
+        assert_eq!(gltf_data.images[0].width, 1);
+        assert_eq!(gltf_data.images[0].pixels, vec![255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn read_primitive_returns_the_generated_attributes_as_warnings() {
+        let positions: Vec<u8> = [0.0f32, 0.0, 0.0, 4.0, 0.0, 0.0, 0.0, 4.0, 0.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+
+        let json = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "buffers": [{{"uri": "data:application/octet-stream;base64,{}", "byteLength": 36}}],
+                "bufferViews": [{{"buffer": 0, "byteLength": 36}}],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [4, 4, 0]}}
+                ],
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}}}]}}]
+            }}"#,
+            base64::encode(&positions)
+        );
+
+        let mut source = MemorySource::new();
+        source.insert("triangle.gltf", json.into_bytes());
+
+        let gltf_data = import_gltf(&source, "triangle.gltf").unwrap();
+        let primitive = gltf_data
+            .document
+            .meshes()
+            .next()
+            .unwrap()
+            .primitives()
+            .next();
+
+        let mut warnings = Vec::new();
+        let data = read_primitive(&gltf_data, 0, &primitive.unwrap(), &mut warnings).unwrap();
+
+        let generated: Vec<_> = warnings
+            .iter()
+            .filter_map(|warning| match warning {
+                LoaderWarning::GeneratedAttribute {
+                    attribute,
+                    fallback,
+                    ..
+                } => Some((*attribute, *fallback)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            generated,
+            vec![
+                (Attribute::Indices, "sequential indices"),
+                (Attribute::TexCoords(0), "planar uvs"),
+                (Attribute::Normals, "flat normals"),
+            ]
+        );
+
+        assert_eq!(data.index_data, vec![0, 1, 2]);
+        assert!(data
+            .vertex_data
+            .iter()
+            .all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn import_gltf_reports_missing_files() {
+        let mut source = MemorySource::new();
+        source.insert(
+            "triangle.gltf",
+            br#"{
+                "asset": {"version": "2.0"},
+                "buffers": [{"uri": "missing.bin", "byteLength": 4}]
+            }"#
+            .to_vec(),
+        );
+
+        assert!(matches!(
+            import_gltf(&source, "triangle.gltf"),
+            Err(LoaderError::Gltf { .. })
+        ));
+        assert!(import_gltf(&source, "other.gltf").is_err());
+    }
+
+    #[test]
+    fn joints_beyond_max_joints_lose_their_influence() {
+        let mut vertex = Vertex {
+            position: [0.0; 3],
+            uv: [0.0; 2],
+            normal: [0.0; 3],
+            uv1: [0.0; 2],
+            joints: [1, MAX_JOINTS as u32, 2, 0],
+            weights: [0.25, 0.5, 0.25, 0.0],
+        };
+
+        assert!(limit_joint_influences(&mut vertex));
+        assert_eq!(vertex.joints, [1, 0, 2, 0]);
+        assert_eq!(vertex.weights, [0.5, 0.0, 0.5, 0.0]);
+
+        // unused slots may point anywhere
+        vertex.joints = [1, 2, 300, 0];
+        vertex.weights = [0.5, 0.5, 0.0, 0.0];
+
+        assert!(!limit_joint_influences(&mut vertex));
+        assert_eq!(vertex.weights, [0.5, 0.5, 0.0, 0.0]);
+
+        // without any joint left the vertex isn't skinned
+        vertex.joints = [MAX_JOINTS as u32, 0, 0, 0];
+        vertex.weights = [1.0, 0.0, 0.0, 0.0];
+
+        assert!(limit_joint_influences(&mut vertex));
+        assert_eq!(vertex.weights, [0.0; 4]);
+    }
+}
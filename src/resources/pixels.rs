@@ -0,0 +1,291 @@
+use crate::gpu::mipmap::srgb_to_linear;
+use std::borrow::Cow;
+use wgpu::TextureFormat;
+
+pub fn extend_data_color(data: &[u8], from_size: usize, to_size: usize) -> Vec<u8> {
+    assert!(to_size > from_size);
+
+    let padding_values = vec![255; to_size - from_size];
+
+    let mut out_data = Vec::with_capacity((data.len() / from_size) * to_size);
+
+    for chunk in data.chunks(from_size) {
+        out_data.extend_from_slice(chunk);
+
+        out_data.extend(&padding_values);
+    }
+
+    out_data
+}
+
+// Turns luminance (and alpha) pixels into rgba pixels by repeating the luminance channel.
+pub fn expand_luma(data: &[u8], channels: usize, channel_size: usize) -> Vec<u8> {
+    assert!(channels == 1 || channels == 2);
+
+    let pixel_size = channels * channel_size;
+    let opaque = vec![255; channel_size];
+
+    let mut out_data = Vec::with_capacity((data.len() / pixel_size) * 4 * channel_size);
+
+    for pixel in data.chunks(pixel_size) {
+        let (luma, alpha) = pixel.split_at(channel_size);
+
+        for _ in 0..3 {
+            out_data.extend_from_slice(luma);
+        }
+
+        if alpha.is_empty() {
+            out_data.extend(&opaque);
+        } else {
+            out_data.extend_from_slice(alpha);
+        }
+    }
+
+    out_data
+}
+
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+
+        return sign | ((mantissa + (1 << (shift - 1))) >> shift) as u16;
+    }
+
+    if exponent >= 31 {
+        return sign | 0x7c00;
+    }
+
+    let half = (((exponent as u32) << 10) | (mantissa >> 13)) + ((mantissa >> 12) & 1);
+
+    sign | half.min(0x7c00) as u16
+}
+
+// Converts native endian 16 bit unorm data to half floats, which unlike the 16 bit
+// integer formats can be sampled with filtering. The first `srgb_channels` channels
+// of each pixel are decoded to linear.
+pub fn unorm16_to_f16(data: &[u8], channels: usize, srgb_channels: usize) -> Vec<u8> {
+    let mut out_data = Vec::with_capacity(data.len());
+
+    for (i, value) in data.chunks_exact(2).enumerate() {
+        let mut value = u16::from_ne_bytes([value[0], value[1]]) as f32 / 65535.0;
+
+        if i % channels < srgb_channels {
+            value = srgb_to_linear(value);
+        }
+
+        out_data.extend_from_slice(&f32_to_f16(value).to_ne_bytes());
+    }
+
+    out_data
+}
+
+// Picks the upload format for glTF image data. Color data (base color, emissive) is
+// stored as sRGB, everything else stays linear.
+pub fn texture_data(image_data: &gltf::image::Data, srgb: bool) -> (Cow<'_, [u8]>, TextureFormat) {
+    use gltf::image::Format;
+
+    let pixels = &image_data.pixels;
+
+    let (rgba8, bgra8) = if srgb {
+        (TextureFormat::Rgba8UnormSrgb, TextureFormat::Bgra8UnormSrgb)
+    } else {
+        (TextureFormat::Rgba8Unorm, TextureFormat::Bgra8Unorm)
+    };
+
+    let srgb_channels = if srgb { 3 } else { 0 };
+
+    match image_data.format {
+        Format::R8 if srgb => (expand_luma(pixels, 1, 1).into(), rgba8),
+        Format::R8 => (pixels.into(), TextureFormat::R8Unorm),
+        Format::R8G8 if srgb => (expand_luma(pixels, 2, 1).into(), rgba8),
+        Format::R8G8 => (pixels.into(), TextureFormat::Rg8Unorm),
+        Format::R8G8B8 => (extend_data_color(pixels, 3, 4).into(), rgba8),
+        Format::R8G8B8A8 => (pixels.into(), rgba8),
+        Format::B8G8R8 => (extend_data_color(pixels, 3, 4).into(), bgra8),
+        Format::B8G8R8A8 => (pixels.into(), bgra8),
+        Format::R16 if srgb => (
+            unorm16_to_f16(&expand_luma(pixels, 1, 2), 4, srgb_channels).into(),
+            TextureFormat::Rgba16Float,
+        ),
+        Format::R16 => (unorm16_to_f16(pixels, 1, 0).into(), TextureFormat::R16Float),
+        Format::R16G16 if srgb => (
+            unorm16_to_f16(&expand_luma(pixels, 2, 2), 4, srgb_channels).into(),
+            TextureFormat::Rgba16Float,
+        ),
+        Format::R16G16 => (
+            unorm16_to_f16(pixels, 2, 0).into(),
+            TextureFormat::Rg16Float,
+        ),
+        Format::R16G16B16 => (
+            unorm16_to_f16(&extend_data_color(pixels, 6, 8), 4, srgb_channels).into(),
+            TextureFormat::Rgba16Float,
+        ),
+        Format::R16G16B16A16 => (
+            unorm16_to_f16(pixels, 4, srgb_channels).into(),
+            TextureFormat::Rgba16Float,
+        ),
+    }
+}
+
+// Decodes png and jpeg images, the formats glTF allows. Without a mime type the
+// format is taken from the extension of `uri` or guessed from the data.
+pub fn decode_image(
+    encoded: &[u8],
+    mime_type: Option<&str>,
+    uri: Option<&str>,
+) -> Result<gltf::image::Data, image::ImageError> {
+    use gltf::image::Format;
+    use image::{DynamicImage, GenericImageView, ImageFormat};
+
+    let extension = uri.and_then(|uri| uri.rsplit('.').next());
+
+    let format = match (mime_type, extension) {
+        (Some("image/png"), _) | (None, Some("png")) => ImageFormat::Png,
+        (Some("image/jpeg"), _) | (None, Some("jpg")) | (None, Some("jpeg")) => ImageFormat::Jpeg,
+        _ => image::guess_format(encoded)?,
+    };
+
+    let image = image::load_from_memory_with_format(encoded, format)?;
+
+    let format = match image {
+        DynamicImage::ImageLuma8(_) => Format::R8,
+        DynamicImage::ImageLumaA8(_) => Format::R8G8,
+        DynamicImage::ImageRgb8(_) => Format::R8G8B8,
+        DynamicImage::ImageRgba8(_) => Format::R8G8B8A8,
+        DynamicImage::ImageBgr8(_) => Format::B8G8R8,
+        DynamicImage::ImageBgra8(_) => Format::B8G8R8A8,
+        DynamicImage::ImageLuma16(_) => Format::R16,
+        DynamicImage::ImageLumaA16(_) => Format::R16G16,
+        DynamicImage::ImageRgb16(_) => Format::R16G16B16,
+        DynamicImage::ImageRgba16(_) => Format::R16G16B16A16,
+    };
+
+    let (width, height) = image.dimensions();
+
+    Ok(gltf::image::Data {
+        format,
+        width,
+        height,
+        pixels: image.to_bytes(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unorm16(values: &[u16]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| v.to_ne_bytes().to_vec())
+            .collect()
+    }
+
+    fn halfs(data: &[u8]) -> Vec<u16> {
+        data.chunks_exact(2)
+            .map(|v| u16::from_ne_bytes([v[0], v[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn extend_data_color_pads_with_opaque() {
+        assert_eq!(
+            extend_data_color(&[1, 2, 3, 4, 5, 6], 3, 4),
+            vec![1, 2, 3, 255, 4, 5, 6, 255]
+        );
+        assert_eq!(
+            extend_data_color(&[1, 2, 3, 4, 5, 6], 6, 8),
+            vec![1, 2, 3, 4, 5, 6, 255, 255]
+        );
+    }
+
+    #[test]
+    fn expand_luma_repeats_luminance() {
+        assert_eq!(expand_luma(&[7, 9], 1, 1), vec![7, 7, 7, 255, 9, 9, 9, 255]);
+        assert_eq!(expand_luma(&[7, 128], 2, 1), vec![7, 7, 7, 128]);
+        assert_eq!(expand_luma(&[1, 2], 1, 2), vec![1, 2, 1, 2, 1, 2, 255, 255]);
+    }
+
+    #[test]
+    fn f32_to_f16_known_values() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+        assert_eq!(f32_to_f16(2.0f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(2.0f32.powi(-14)), 0x0400);
+        assert_eq!(f32_to_f16(1.0e-10), 0x0000);
+    }
+
+    #[test]
+    fn unorm16_to_f16_maps_range() {
+        let data = unorm16(&[0, 65535, 0, 65535]);
+
+        assert_eq!(
+            halfs(&unorm16_to_f16(&data, 4, 0)),
+            vec![0, 0x3c00, 0, 0x3c00]
+        );
+    }
+
+    #[test]
+    fn unorm16_to_f16_decodes_srgb_but_not_alpha() {
+        let data = unorm16(&[32768, 32768, 32768, 32768]);
+
+        let linear = f32_to_f16(srgb_to_linear(32768.0 / 65535.0));
+        let alpha = f32_to_f16(32768.0 / 65535.0);
+
+        assert_eq!(
+            halfs(&unorm16_to_f16(&data, 4, 3)),
+            vec![linear, linear, linear, alpha]
+        );
+    }
+
+    #[test]
+    fn texture_data_chooses_color_space() {
+        let image_data = gltf::image::Data {
+            format: gltf::image::Format::R8G8B8,
+            width: 1,
+            height: 1,
+            pixels: vec![10, 20, 30],
+        };
+
+        let (pixels, format) = texture_data(&image_data, true);
+        assert_eq!(format, TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(&pixels[..], &[10, 20, 30, 255]);
+
+        let (_, format) = texture_data(&image_data, false);
+        assert_eq!(format, TextureFormat::Rgba8Unorm);
+    }
+
+    #[test]
+    fn texture_data_uses_filterable_16_bit_formats() {
+        let image_data = gltf::image::Data {
+            format: gltf::image::Format::R16,
+            width: 1,
+            height: 1,
+            pixels: unorm16(&[65535]),
+        };
+
+        let (pixels, format) = texture_data(&image_data, false);
+        assert_eq!(format, TextureFormat::R16Float);
+        assert_eq!(halfs(&pixels), vec![0x3c00]);
+
+        let (pixels, format) = texture_data(&image_data, true);
+        assert_eq!(format, TextureFormat::Rgba16Float);
+        assert_eq!(halfs(&pixels), vec![0x3c00; 4]);
+    }
+}
@@ -1,8 +1,30 @@
-use super::scene::Scene;
+use super::{scene::Scene, LoaderWarning};
+use serde::{Deserialize, Serialize};
 use slotmap::DefaultKey;
+use std::path::PathBuf;
 
 #[derive(Debug, Default)]
 pub struct Prefab {
     pub root: DefaultKey,
     pub scene: Scene,
+    pub warnings: Vec<LoaderWarning>,
+    // files besides the glTF file the prefab was read from, see `MapReloader`
+    pub dependencies: Vec<PathBuf>,
+}
+
+// Picks one of the scenes of a glTF file, by index or by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SceneSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl std::fmt::Display for SceneSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SceneSelector::Index(index) => write!(f, "{}", index),
+            SceneSelector::Name(name) => write!(f, "'{}'", name),
+        }
+    }
 }
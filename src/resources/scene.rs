@@ -1,15 +1,30 @@
-use super::model::load_gltf;
 use crate::{
-    components::{CameraComponent, MeshComponent, PlayerComponent, TransformComponent},
+    components::{
+        AnimationComponent, CameraComponent, ExtrasComponent, LightComponent, MeshComponent,
+        NameComponent, PlayerComponent, SkinComponent, TransformComponent,
+    },
     gpu::Context,
     resources::{
-        manager::ResourceManager,
-        map::{Map, Node},
-        prefab::Prefab,
+        manager::{PrefabState, ResourceManager},
+        map::{Map, Node, PrefabSource},
+        LoaderError,
     },
 };
 use slotmap::{DefaultKey, HopSlotMap, SecondaryMap};
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+// Entities created by loading a map into a scene. The root holds the references to
+// the prefabs of the map.
+#[derive(Debug)]
+pub struct MapInstance {
+    pub root: DefaultKey,
+    // entities of the map nodes instancing a prefab, with the index of the prefab in
+    // the map
+    pub prefab_nodes: Vec<(DefaultKey, usize)>,
+}
 
 #[derive(Debug, Default)]
 pub struct Scene {
@@ -17,7 +32,17 @@ pub struct Scene {
     pub transforms_sorted: Vec<DefaultKey>,
     pub meshes: SecondaryMap<DefaultKey, MeshComponent>,
     pub cameras: SecondaryMap<DefaultKey, CameraComponent>,
+    pub lights: SecondaryMap<DefaultKey, LightComponent>,
     pub players: SecondaryMap<DefaultKey, PlayerComponent>,
+    pub skins: SecondaryMap<DefaultKey, SkinComponent>,
+    pub animations: SecondaryMap<DefaultKey, AnimationComponent>,
+    pub names: SecondaryMap<DefaultKey, NameComponent>,
+    pub extras: SecondaryMap<DefaultKey, ExtrasComponent>,
+    pub pending_prefabs: Vec<(DefaultKey, usize)>,
+    // prefab references held by the roots of loaded maps, released together with them
+    map_prefabs: SecondaryMap<DefaultKey, Vec<usize>>,
+    // roots of the prefab copies below map nodes
+    prefab_copies: SecondaryMap<DefaultKey, DefaultKey>,
 }
 
 impl Scene {
@@ -33,10 +58,78 @@ impl Scene {
         key
     }
 
-    pub fn copy_from(&mut self, other: &Scene, root: &DefaultKey) -> DefaultKey {
+    // Removes the entity together with all of its descendants.
+    pub fn destroy_entity(&mut self, resource_manager: &mut ResourceManager, entity: DefaultKey) {
+        let mut destroyed = HashSet::new();
+        destroyed.insert(entity);
+
+        for key in &self.transforms_sorted {
+            if let Some(parent) = self.transforms.get(*key).and_then(|t| t.parent) {
+                if destroyed.contains(&parent) {
+                    destroyed.insert(*key);
+                }
+            }
+        }
+
+        for key in &destroyed {
+            self.release_entity(resource_manager, *key);
+        }
+
+        self.transforms_sorted
+            .retain(|key| !destroyed.contains(key));
+        self.pending_prefabs
+            .retain(|(key, _)| !destroyed.contains(key));
+    }
+
+    // Removes the components of the entity and releases the resources they reference.
+    fn release_entity(&mut self, resource_manager: &mut ResourceManager, entity: DefaultKey) {
+        self.transforms.remove(entity);
+
+        if let Some(mesh) = self.meshes.remove(entity) {
+            resource_manager.release_mesh(&mesh);
+        }
+
+        if let Some(prefab_ids) = self.map_prefabs.remove(entity) {
+            for prefab_id in prefab_ids {
+                resource_manager.release_prefab(prefab_id);
+            }
+        }
+
+        self.prefab_copies.remove(entity);
+        self.cameras.remove(entity);
+        self.lights.remove(entity);
+        self.players.remove(entity);
+        self.skins.remove(entity);
+        self.animations.remove(entity);
+        self.names.remove(entity);
+        self.extras.remove(entity);
+    }
+
+    // Destroys every entity and releases their resources, e.g. before switching to
+    // another level. Unlike `unload_map` this also takes entities no map created.
+    #[allow(dead_code)]
+    pub fn clear(&mut self, resource_manager: &mut ResourceManager) {
+        for key in std::mem::take(&mut self.transforms_sorted) {
+            self.release_entity(resource_manager, key);
+        }
+
+        *self = Scene::default();
+    }
+
+    pub fn copy_from(
+        &mut self,
+        resource_manager: &mut ResourceManager,
+        other: &Scene,
+        root: &DefaultKey,
+    ) -> DefaultKey {
         let mut parent_mapping = HashMap::<DefaultKey, DefaultKey>::new();
 
-        for (other_key, transform) in &other.transforms {
+        for &other_key in &other.transforms_sorted {
+            let transform = match other.transforms.get(other_key) {
+                Some(transform) => transform,
+                None => continue,
+            };
+
             let self_key;
             if let Some(other_parent) = &transform.parent {
                 self_key = self.transforms.insert(TransformComponent {
@@ -52,6 +145,8 @@ impl Scene {
             parent_mapping.insert(other_key, self_key);
 
             if let Some(other_mesh) = other.meshes.get(other_key) {
+                resource_manager.retain_mesh(other_mesh);
+
                 self.meshes.insert(self_key, other_mesh.clone());
             }
 
@@ -59,44 +154,193 @@ impl Scene {
                 self.cameras.insert(self_key, *other_camera);
             }
 
+            if let Some(other_light) = other.lights.get(other_key) {
+                self.lights.insert(self_key, *other_light);
+            }
+
             if let Some(other_player) = other.players.get(other_key) {
                 self.players.insert(self_key, *other_player);
             }
+
+            if let Some(other_name) = other.names.get(other_key) {
+                self.names.insert(self_key, other_name.clone());
+            }
+
+            if let Some(other_extras) = other.extras.get(other_key) {
+                self.extras.insert(self_key, other_extras.clone());
+            }
+        }
+
+        // skins and animations can refer to entities which were copied after them
+        let remap = |keys: &[DefaultKey]| keys.iter().map(|key| parent_mapping[key]).collect();
+
+        for (other_key, other_skin) in &other.skins {
+            if let Some(&self_key) = parent_mapping.get(&other_key) {
+                self.skins.insert(
+                    self_key,
+                    SkinComponent {
+                        joints: remap(&other_skin.joints),
+                        ..other_skin.clone()
+                    },
+                );
+            }
+        }
+
+        for (other_key, other_animation) in &other.animations {
+            if let Some(&self_key) = parent_mapping.get(&other_key) {
+                self.animations.insert(
+                    self_key,
+                    AnimationComponent {
+                        targets: remap(&other_animation.targets),
+                        ..other_animation.clone()
+                    },
+                );
+            }
         }
 
         *parent_mapping.get(root).unwrap()
     }
 
+    #[allow(dead_code)]
+    pub fn find_by_name(&self, name: &str) -> Option<DefaultKey> {
+        self.transforms_sorted
+            .iter()
+            .copied()
+            .find(|&key| self.names.get(key).map(|n| n.name.as_str()) == Some(name))
+    }
+
+    // Finds an entity by the names along its hierarchy, e.g. "root/door/handle". The
+    // first name can be anywhere in the scene and unnamed entities in between, like the
+    // roots of prefab instances, are skipped.
+    pub fn find_by_path(&self, path: &str) -> Option<DefaultKey> {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        if segments.is_empty() {
+            return None;
+        }
+
+        // number of leading segments matched by the ancestors of each entity
+        let mut matched = HashMap::<DefaultKey, Vec<usize>>::new();
+
+        for &key in &self.transforms_sorted {
+            let transform = match self.transforms.get(key) {
+                Some(transform) => transform,
+                None => continue,
+            };
+
+            let mut counts = transform
+                .parent
+                .and_then(|parent| matched.get(&parent).cloned())
+                .unwrap_or_default();
+
+            if let Some(name) = self.names.get(key) {
+                counts = counts
+                    .into_iter()
+                    .filter(|&count| segments[count] == name.name)
+                    .map(|count| count + 1)
+                    .collect();
+
+                if segments[0] == name.name && !counts.contains(&1) {
+                    counts.push(1);
+                }
+
+                if counts.contains(&segments.len()) {
+                    return Some(key);
+                }
+            }
+
+            if !counts.is_empty() {
+                matched.insert(key, counts);
+            }
+        }
+
+        None
+    }
+
+    // Copies the prefab below the map node `entity`, or shows the placeholder until the
+    // prefab is loaded.
+    fn instance_prefab(
+        &mut self,
+        resource_manager: &mut ResourceManager,
+        placeholder: &MeshComponent,
+        entity: DefaultKey,
+        prefab_id: usize,
+    ) {
+        if let Some(prefab) = resource_manager.prefab(prefab_id) {
+            let root = self.copy_from(resource_manager, &prefab.scene, &prefab.root);
+
+            self.transforms.get_mut(root).unwrap().parent = Some(entity);
+            self.prefab_copies.insert(entity, root);
+        } else {
+            resource_manager.retain_mesh(placeholder);
+            self.meshes.insert(entity, placeholder.clone());
+
+            self.pending_prefabs.push((entity, prefab_id));
+        }
+    }
+
+    // Removes the prefab copy or placeholder below the map node `entity`.
+    fn clear_prefab_instance(
+        &mut self,
+        resource_manager: &mut ResourceManager,
+        entity: DefaultKey,
+    ) {
+        if let Some(root) = self.prefab_copies.remove(entity) {
+            self.destroy_entity(resource_manager, root);
+        }
+
+        if let Some(placeholder) = self.meshes.remove(entity) {
+            resource_manager.release_mesh(&placeholder);
+        }
+
+        self.pending_prefabs.retain(|(key, _)| *key != entity);
+    }
+
     fn load_node(
         &mut self,
-        prefabs: &[Prefab],
+        resource_manager: &mut ResourceManager,
+        placeholder: &MeshComponent,
+        prefab_ids: &[usize],
         node: &Node,
         parent: Option<DefaultKey>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let transform = TransformComponent {
+        prefab_nodes: &mut Vec<(DefaultKey, usize)>,
+    ) -> Result<DefaultKey, LoaderError> {
+        let new_id = self.create_entity(TransformComponent {
             translation: node.translation,
             rotation: node.rotation,
             scale: node.scale,
             parent,
             ..Default::default()
-        };
+        });
 
-        let new_id = if let Some(prefab_id) = node.prefab_id {
-            let prefab = &prefabs[prefab_id];
-            let new_root = self.copy_from(&prefab.scene, &prefab.root);
+        // in range, `instantiate_map` validated the map
+        if let Some(prefab_index) = node.prefab_id {
+            self.instance_prefab(
+                resource_manager,
+                placeholder,
+                new_id,
+                prefab_ids[prefab_index],
+            );
 
-            *self.transforms.get_mut(new_root).unwrap() = transform;
+            prefab_nodes.push((new_id, prefab_index));
+        }
 
-            new_root
-        } else {
-            self.create_entity(transform)
-        };
+        if let Some(name) = &node.name {
+            self.names.insert(new_id, NameComponent::new(name));
+        }
 
         for child in &node.children {
-            self.load_node(prefabs, child, Some(new_id))?;
+            self.load_node(
+                resource_manager,
+                placeholder,
+                prefab_ids,
+                child,
+                Some(new_id),
+                prefab_nodes,
+            )?;
         }
 
-        Ok(())
+        Ok(new_id)
     }
 
     pub fn load_map(
@@ -104,15 +348,512 @@ impl Scene {
         context: &Context,
         resource_manager: &mut ResourceManager,
         map: &Map,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut prefabs = Vec::new();
+    ) -> Result<MapInstance, LoaderError> {
+        let mut prefab_ids = Vec::new();
+
+        for source in &map.prefabs {
+            match resource_manager.load_prefab(context, source.path(), source.scene()) {
+                Ok(prefab_id) => prefab_ids.push(prefab_id),
+                Err(err) => {
+                    for prefab_id in prefab_ids {
+                        resource_manager.release_prefab(prefab_id);
+                    }
+
+                    return Err(err);
+                }
+            }
+        }
+
+        let placeholder = resource_manager.placeholder_mesh(context);
+
+        self.instantiate_map(resource_manager, &placeholder, map, prefab_ids)
+    }
+
+    // Returns immediately, prefab instances show a placeholder mesh until
+    // `resolve_pending_prefabs` swaps in the loaded prefab.
+    pub fn load_map_async(
+        &mut self,
+        context: &Context,
+        resource_manager: &mut ResourceManager,
+        map: &Map,
+    ) -> Result<MapInstance, LoaderError> {
+        let prefab_ids: Vec<usize> = map
+            .prefabs
+            .iter()
+            .map(|source| resource_manager.load_prefab_async(source.path(), source.scene()))
+            .collect();
+
+        let placeholder = resource_manager.placeholder_mesh(context);
+
+        self.instantiate_map(resource_manager, &placeholder, map, prefab_ids)
+    }
+
+    // Creates the entities of `map`, whose root takes over the references to the
+    // prefabs in `prefab_ids`. Maps which fail validation release them instead and
+    // create nothing.
+    fn instantiate_map(
+        &mut self,
+        resource_manager: &mut ResourceManager,
+        placeholder: &MeshComponent,
+        map: &Map,
+        prefab_ids: Vec<usize>,
+    ) -> Result<MapInstance, LoaderError> {
+        if let Err(err) = map.validate(&map.path) {
+            for prefab_id in prefab_ids {
+                resource_manager.release_prefab(prefab_id);
+            }
+
+            return Err(LoaderError::Map(err));
+        }
+
+        let mut prefab_nodes = Vec::new();
+
+        let root = self.load_node(
+            resource_manager,
+            placeholder,
+            &prefab_ids,
+            &map.root,
+            None,
+            &mut prefab_nodes,
+        )?;
+
+        self.map_prefabs.insert(root, prefab_ids);
+
+        Ok(MapInstance { root, prefab_nodes })
+    }
+
+    // Prefabs the map instance holds, in the order of the prefabs of the map.
+    pub fn map_prefab_ids(&self, instance: &MapInstance) -> Option<&[usize]> {
+        self.map_prefabs.get(instance.root).map(Vec::as_slice)
+    }
+
+    // Loads prefab `prefab_index` of the map again, e.g. after its file changed. Only
+    // the map nodes instancing it are touched, their entities stay the same.
+    pub fn reload_map_prefab(
+        &mut self,
+        context: &Context,
+        resource_manager: &mut ResourceManager,
+        instance: &MapInstance,
+        prefab_index: usize,
+        source: &PrefabSource,
+    ) {
+        if !self.map_prefabs.contains_key(instance.root) {
+            return;
+        }
+
+        let prefab_id = resource_manager.load_prefab_async(source.path(), source.scene());
+        let placeholder = resource_manager.placeholder_mesh(context);
+
+        self.replace_map_prefab(
+            resource_manager,
+            &placeholder,
+            instance,
+            prefab_index,
+            prefab_id,
+        );
+    }
+
+    // Makes the map nodes instancing prefab `prefab_index` show `prefab_id` instead,
+    // whose reference is taken over by the map.
+    fn replace_map_prefab(
+        &mut self,
+        resource_manager: &mut ResourceManager,
+        placeholder: &MeshComponent,
+        instance: &MapInstance,
+        prefab_index: usize,
+        prefab_id: usize,
+    ) {
+        for &(entity, node_prefab_index) in &instance.prefab_nodes {
+            if node_prefab_index == prefab_index && self.transforms.contains_key(entity) {
+                self.clear_prefab_instance(resource_manager, entity);
+                self.instance_prefab(resource_manager, placeholder, entity, prefab_id);
+            }
+        }
+
+        let old_prefab_id = std::mem::replace(
+            &mut self.map_prefabs[instance.root][prefab_index],
+            prefab_id,
+        );
+
+        resource_manager.release_prefab(old_prefab_id);
+    }
+
+    // Destroys the entities of the map and releases its prefabs.
+    pub fn unload_map(&mut self, resource_manager: &mut ResourceManager, instance: MapInstance) {
+        self.destroy_entity(resource_manager, instance.root);
+    }
+
+    // Swaps the placeholders of prefabs which finished loading for the prefabs. Entities
+    // whose prefab failed to load keep the placeholder and are returned with the error.
+    pub fn resolve_pending_prefabs(
+        &mut self,
+        resource_manager: &mut ResourceManager,
+    ) -> Vec<(DefaultKey, Rc<LoaderError>)> {
+        let pending_prefabs = std::mem::take(&mut self.pending_prefabs);
+        let mut failed = Vec::new();
+
+        for (entity, prefab_id) in pending_prefabs {
+            if !self.transforms.contains_key(entity) {
+                continue;
+            }
+
+            match resource_manager.prefab_state(prefab_id) {
+                PrefabState::Loading => self.pending_prefabs.push((entity, prefab_id)),
+                PrefabState::Loaded(prefab) => {
+                    if let Some(placeholder) = self.meshes.remove(entity) {
+                        resource_manager.release_mesh(&placeholder);
+                    }
+
+                    let root = self.copy_from(resource_manager, &prefab.scene, &prefab.root);
+
+                    self.transforms.get_mut(root).unwrap().parent = Some(entity);
+                    self.prefab_copies.insert(entity, root);
+                }
+                PrefabState::Failed(err) => failed.push((entity, err)),
+            }
+        }
+
+        failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::MeshPrimitive,
+        resources::{
+            map::MapError,
+            prefab::{Prefab, SceneSelector},
+        },
+    };
+
+    fn mesh(geometry_id: usize) -> MeshComponent {
+        let mut mesh = MeshComponent::new();
+
+        mesh.primitives.push(MeshPrimitive {
+            geometry_id,
+            pipeline_id: 0,
+            material: Default::default(),
+            material_bind_group_id: 0,
+        });
 
-        for prefab_id in &map.prefabs {
-            prefabs.push(load_gltf(context, resource_manager, prefab_id)?);
+        mesh
+    }
+
+    fn prefab_map() -> Map {
+        Map {
+            prefabs: vec![PrefabSource::Path(String::from("door.gltf"))],
+            root: Node {
+                children: vec![Node {
+                    prefab_id: Some(0),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
         }
+    }
+
+    #[test]
+    fn destroying_a_copy_releases_its_meshes() {
+        let mut resource_manager = ResourceManager::default();
+
+        let mut prefab = Scene::new();
+        let root = prefab.create_entity(TransformComponent::default());
+        let child = prefab.create_entity(TransformComponent {
+            parent: Some(root),
+            ..Default::default()
+        });
+        prefab.meshes.insert(child, mesh(7));
+
+        // the reference held by the prefab itself
+        resource_manager.retain_mesh(&mesh(7));
+
+        let mut scene = Scene::new();
+        let copy = scene.copy_from(&mut resource_manager, &prefab, &root);
+
+        assert_eq!(scene.transforms.len(), 2);
+        assert_eq!(resource_manager.geometry_ref_count(7), 2);
+
+        scene.destroy_entity(&mut resource_manager, copy);
+
+        assert!(scene.transforms.is_empty());
+        assert!(scene.meshes.is_empty());
+        assert_eq!(resource_manager.geometry_ref_count(7), 1);
+    }
+
+    #[test]
+    fn unloading_a_map_frees_its_prefabs() {
+        let mut resource_manager = ResourceManager::default();
+
+        let mut prefab = Prefab::default();
+        prefab.root = prefab.scene.create_entity(TransformComponent::default());
+
+        let prefab_id =
+            resource_manager.insert_prefab("door.gltf", None, PrefabState::Loaded(Rc::new(prefab)));
+
+        let mut scene = Scene::new();
+        let instance = scene
+            .instantiate_map(
+                &mut resource_manager,
+                &MeshComponent::new(),
+                &prefab_map(),
+                vec![prefab_id],
+            )
+            .unwrap();
+
+        // map root, prefab node and the root of the prefab copied below it
+        assert_eq!(scene.transforms.len(), 3);
+        assert_eq!(resource_manager.prefab_ref_count(prefab_id), 1);
+
+        scene.unload_map(&mut resource_manager, instance);
+
+        assert!(scene.transforms.is_empty());
+        assert_eq!(resource_manager.prefab_ref_count(prefab_id), 0);
+    }
+
+    #[test]
+    fn clear_releases_map_prefabs_and_placeholders() {
+        let mut resource_manager = ResourceManager::default();
+
+        let placeholder = mesh(3);
+        resource_manager.retain_mesh(&placeholder);
+
+        // still loading, so the map shows the placeholder
+        let prefab_id = resource_manager.insert_prefab("door.gltf", None, PrefabState::Loading);
+
+        let mut scene = Scene::new();
+        scene
+            .instantiate_map(
+                &mut resource_manager,
+                &placeholder,
+                &prefab_map(),
+                vec![prefab_id],
+            )
+            .unwrap();
+
+        assert_eq!(scene.pending_prefabs.len(), 1);
+        assert_eq!(resource_manager.geometry_ref_count(3), 2);
+
+        scene.clear(&mut resource_manager);
+
+        assert!(scene.pending_prefabs.is_empty());
+        assert_eq!(resource_manager.geometry_ref_count(3), 1);
+        assert_eq!(resource_manager.prefab_ref_count(prefab_id), 0);
+    }
+
+    #[test]
+    fn failed_prefabs_stop_being_pending() {
+        let mut resource_manager = ResourceManager::default();
+
+        let err = LoaderError::MissingScene {
+            path: "door.gltf".into(),
+            scene: SceneSelector::Index(1),
+        };
+        let prefab_id =
+            resource_manager.insert_prefab("door.gltf", None, PrefabState::Failed(Rc::new(err)));
+
+        let mut scene = Scene::new();
+        scene
+            .instantiate_map(
+                &mut resource_manager,
+                &MeshComponent::new(),
+                &prefab_map(),
+                vec![prefab_id],
+            )
+            .unwrap();
+
+        let entity = scene.pending_prefabs[0].0;
+
+        let failed = scene.resolve_pending_prefabs(&mut resource_manager);
+
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, entity);
+        assert!(scene.pending_prefabs.is_empty());
+        assert!(scene
+            .resolve_pending_prefabs(&mut resource_manager)
+            .is_empty());
+    }
+
+    #[test]
+    fn maps_referencing_undeclared_prefabs_are_rejected() {
+        let mut resource_manager = ResourceManager::default();
+
+        let prefab_id = resource_manager.insert_prefab("door.gltf", None, PrefabState::Loading);
+
+        let mut map = prefab_map();
+        map.root.children[0].prefab_id = Some(1);
+
+        let mut scene = Scene::new();
+        let result = scene.instantiate_map(
+            &mut resource_manager,
+            &MeshComponent::new(),
+            &map,
+            vec![prefab_id],
+        );
+
+        assert!(matches!(
+            result,
+            Err(LoaderError::Map(MapError::InvalidPrefabId {
+                prefab_id: 1,
+                ..
+            }))
+        ));
+        assert!(scene.transforms.is_empty());
+        assert_eq!(resource_manager.prefab_ref_count(prefab_id), 0);
+    }
+
+    fn loaded_prefab(part: &str) -> PrefabState {
+        let mut prefab = Prefab::default();
+        prefab.root = prefab.scene.create_entity(TransformComponent::default());
+
+        let part_entity = prefab.scene.create_entity(TransformComponent {
+            parent: Some(prefab.root),
+            ..Default::default()
+        });
+        prefab
+            .scene
+            .names
+            .insert(part_entity, NameComponent::new(part));
+
+        PrefabState::Loaded(Rc::new(prefab))
+    }
+
+    #[test]
+    fn replacing_a_map_prefab_only_touches_the_nodes_using_it() {
+        let mut resource_manager = ResourceManager::default();
+
+        let door_id = resource_manager.insert_prefab("door.gltf", None, loaded_prefab("door"));
+        let lamp_id = resource_manager.insert_prefab("lamp.gltf", None, loaded_prefab("lamp"));
+
+        let named = |name: &str, prefab_id, children| Node {
+            name: Some(String::from(name)),
+            prefab_id,
+            children,
+            ..Default::default()
+        };
+
+        let map = Map {
+            prefabs: vec![
+                PrefabSource::Path(String::from("door.gltf")),
+                PrefabSource::Path(String::from("lamp.gltf")),
+            ],
+            root: Node {
+                children: vec![
+                    named("front", Some(0), vec![named("sign", None, Vec::new())]),
+                    named("light", Some(1), Vec::new()),
+                    named("back", Some(0), Vec::new()),
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut scene = Scene::new();
+        let instance = scene
+            .instantiate_map(
+                &mut resource_manager,
+                &MeshComponent::new(),
+                &map,
+                vec![door_id, lamp_id],
+            )
+            .unwrap();
+
+        let front = scene.find_by_name("front").unwrap();
+        let light = scene.find_by_name("light").unwrap();
+        let back = scene.find_by_name("back").unwrap();
+        let sign = scene.find_by_name("sign").unwrap();
+        let lamp = scene.find_by_name("lamp").unwrap();
+        let old_front_copy = scene.prefab_copies[front];
+
+        assert_eq!(
+            instance.prefab_nodes,
+            vec![(front, 0), (light, 1), (back, 0)]
+        );
+
+        resource_manager.evict_prefab("door.gltf");
+        let new_door_id =
+            resource_manager.insert_prefab("door.gltf", None, loaded_prefab("new door"));
+
+        scene.replace_map_prefab(
+            &mut resource_manager,
+            &MeshComponent::new(),
+            &instance,
+            0,
+            new_door_id,
+        );
+
+        // every entity of the map and of the untouched prefab is still there
+        for &entity in &[instance.root, front, light, back, sign, lamp] {
+            assert!(scene.transforms.contains_key(entity));
+        }
+
+        assert_eq!(scene.transforms[sign].parent, Some(front));
+        assert!(!scene.transforms.contains_key(old_front_copy));
+        assert_eq!(scene.find_by_name("door"), None);
+        assert!(scene.find_by_path("front/new door").is_some());
+        assert!(scene.find_by_path("back/new door").is_some());
+
+        assert_eq!(resource_manager.prefab_ref_count(door_id), 0);
+        assert_eq!(resource_manager.prefab_ref_count(new_door_id), 1);
+        assert_eq!(resource_manager.prefab_ref_count(lamp_id), 1);
+    }
+
+    fn add_node(scene: &mut Scene, parent: Option<DefaultKey>, name: Option<&str>) -> DefaultKey {
+        let entity = scene.create_entity(TransformComponent {
+            parent,
+            ..Default::default()
+        });
+
+        if let Some(name) = name {
+            scene.names.insert(entity, NameComponent::new(name));
+        }
+
+        entity
+    }
+
+    #[test]
+    fn find_by_path_follows_the_names_down_the_hierarchy() {
+        let mut scene = Scene::new();
+
+        let level = add_node(&mut scene, None, Some("level"));
+        let house = add_node(&mut scene, Some(level), Some("house"));
+        let door = add_node(&mut scene, Some(house), Some("door"));
+        let door_handle = add_node(&mut scene, Some(door), Some("handle"));
+        let window = add_node(&mut scene, Some(house), Some("window"));
+        let window_handle = add_node(&mut scene, Some(window), Some("handle"));
+
+        // like a map node with a prefab copied below it
+        let shed = add_node(&mut scene, Some(level), Some("shed"));
+        let prefab_root = add_node(&mut scene, Some(shed), None);
+        let shed_door = add_node(&mut scene, Some(prefab_root), Some("door"));
+
+        assert_eq!(scene.find_by_path("level/house/door"), Some(door));
+        assert_eq!(scene.find_by_path("/house/door/"), Some(door));
+        assert_eq!(scene.find_by_path("house/door/handle"), Some(door_handle));
+        assert_eq!(
+            scene.find_by_path("house/window/handle"),
+            Some(window_handle)
+        );
+        assert_eq!(scene.find_by_path("window/handle"), Some(window_handle));
+        assert_eq!(scene.find_by_path("shed/door"), Some(shed_door));
+        assert_eq!(scene.find_by_path("level/shed/door"), Some(shed_door));
+    }
+
+    #[test]
+    fn find_by_path_returns_none_without_a_match() {
+        let mut scene = Scene::new();
 
-        self.load_node(&prefabs, &map.root, None)?;
+        let house = add_node(&mut scene, None, Some("house"));
+        let door = add_node(&mut scene, Some(house), Some("door"));
+        add_node(&mut scene, Some(door), Some("handle"));
 
-        Ok(())
+        assert_eq!(scene.find_by_path(""), None);
+        assert_eq!(scene.find_by_path("/"), None);
+        assert_eq!(scene.find_by_path("//"), None);
+        assert_eq!(scene.find_by_path("house/handle"), None);
+        assert_eq!(scene.find_by_path("house/door/knob"), None);
+        assert_eq!(scene.find_by_path("door/house"), None);
     }
 }
@@ -0,0 +1,437 @@
+[[location(0)]]
+var<in> in_position: vec3<f32>;
+
+[[location(1)]]
+var<in> in_uv_vs: vec2<f32>;
+
+[[location(2)]]
+var<in> in_normal_vs: vec3<f32>;
+
+[[location(3)]]
+var<in> in_uv1_vs: vec2<f32>;
+
+[[location(4)]]
+var<in> in_joints_vs: vec4<u32>;
+
+[[location(5)]]
+var<in> in_weights_vs: vec4<f32>;
+
+[[location(0)]]
+var<out> out_uv: vec2<f32>;
+
+[[location(1)]]
+var<out> out_normal: vec3<f32>;
+
+[[location(2)]]
+var<out> out_uv1: vec2<f32>;
+
+[[location(3)]]
+var<out> out_world_position: vec3<f32>;
+
+[[builtin(position)]]
+var<out> out_position: vec4<f32>;
+
+
+[[block]]
+struct Globals {
+    view_proj: mat4x4<f32>;
+    camera_position: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> u_globals: Globals;
+
+
+[[block]]
+struct Locals {
+    model: mat4x4<f32>;
+    // inverse transpose of the model matrix
+    normal: mat4x4<f32>;
+};
+
+[[group(1), binding(0)]]
+var r_locals: Locals;
+
+
+[[block]]
+struct Joints {
+    matrices: [[stride(64)]] array<mat4x4<f32>, 64u>;
+};
+
+[[group(1), binding(1)]]
+var r_joints: Joints;
+
+
+[[stage(vertex)]]
+fn vs_main() {
+    out_uv = in_uv_vs;
+    out_uv1 = in_uv1_vs;
+
+    var skin: mat4x4<f32> = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0)
+    );
+    if (in_weights_vs.x + in_weights_vs.y + in_weights_vs.z + in_weights_vs.w > 0.0) {
+        skin =
+            r_joints.matrices[in_joints_vs.x] * in_weights_vs.x +
+            r_joints.matrices[in_joints_vs.y] * in_weights_vs.y +
+            r_joints.matrices[in_joints_vs.z] * in_weights_vs.z +
+            r_joints.matrices[in_joints_vs.w] * in_weights_vs.w;
+    }
+
+    const world_position: vec4<f32> = r_locals.model * skin * vec4<f32>(in_position, 1.0);
+
+    // the joint matrices are expected to be free of non-uniform scale
+    out_normal = (r_locals.normal * skin * vec4<f32>(in_normal_vs, 0.0)).xyz;
+    out_world_position = world_position.xyz;
+    out_position = u_globals.view_proj * world_position;
+}
+
+
+[[location(0)]]
+var<in> in_uv_fs: vec2<f32>;
+
+[[location(1)]]
+var<in> in_normal_fs: vec3<f32>;
+
+[[location(2)]]
+var<in> in_uv1_fs: vec2<f32>;
+
+[[location(3)]]
+var<in> in_world_position_fs: vec3<f32>;
+
+[[location(0)]]
+var<out> out_color: vec4<f32>;
+
+
+[[block]]
+struct Light {
+    position: vec4<f32>;
+    direction: vec4<f32>;
+    color: vec4<f32>;
+    kind: u32;
+    range: f32;
+    inner_cone_cos: f32;
+    outer_cone_cos: f32;
+    shadow_map: u32;
+    shadow_map_count: u32;
+};
+
+[[block]]
+struct Lights {
+    lights: [[stride(80)]] array<Light, 16u>;
+    ambient: vec4<f32>;
+    count: u32;
+};
+
+[[group(0), binding(1)]]
+var<uniform> u_lights: Lights;
+
+
+[[block]]
+struct Shadows {
+    view_projs: [[stride(64)]] array<mat4x4<f32>, 16u>;
+};
+
+[[group(0), binding(2)]]
+var<uniform> u_shadows: Shadows;
+
+[[group(0), binding(3)]]
+var r_shadow_atlas: texture_depth_2d;
+[[group(0), binding(4)]]
+var r_shadow_sampler: sampler_comparison;
+
+
+[[block]]
+struct Primitive {
+    color: vec4<f32>;
+    emissive: vec4<f32>;
+    color_tex_coord: u32;
+    metallic: f32;
+    roughness: f32;
+    receive_shadows: u32;
+    metallic_roughness_tex_coord: u32;
+    normal_tex_coord: u32;
+    occlusion_tex_coord: u32;
+    emissive_tex_coord: u32;
+    normal_scale: f32;
+    occlusion_strength: f32;
+    alpha_cutoff: f32;
+    double_sided: u32;
+};
+
+[[group(2), binding(0)]]
+var r_primitive: Primitive;
+
+[[group(3), binding(0)]]
+var r_color: texture_2d<f32>;
+[[group(3), binding(1)]]
+var r_sampler: sampler;
+[[group(3), binding(2)]]
+var r_metallic_roughness: texture_2d<f32>;
+[[group(3), binding(3)]]
+var r_metallic_roughness_sampler: sampler;
+[[group(3), binding(4)]]
+var r_normal: texture_2d<f32>;
+[[group(3), binding(5)]]
+var r_normal_sampler: sampler;
+[[group(3), binding(6)]]
+var r_occlusion: texture_2d<f32>;
+[[group(3), binding(7)]]
+var r_occlusion_sampler: sampler;
+[[group(3), binding(8)]]
+var r_emissive: texture_2d<f32>;
+[[group(3), binding(9)]]
+var r_emissive_sampler: sampler;
+
+
+const PI: f32 = 3.14159265;
+
+const LIGHT_KIND_DIRECTIONAL: u32 = 0u;
+const LIGHT_KIND_SPOT: u32 = 2u;
+
+// shadow maps per row of the atlas and size of a texel of the atlas
+const SHADOW_ATLAS_TILES: u32 = 4u;
+const SHADOW_TEXEL: f32 = 0.000244140625;
+
+// Trowbridge-Reitz GGX normal distribution
+fn distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    const alpha2: f32 = alpha * alpha;
+    const d: f32 = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+
+    return alpha2 / (PI * d * d);
+}
+
+// Smith-GGX height-correlated visibility, includes the 1 / (4 n.l n.v) of the BRDF
+fn visibility(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+    const alpha2: f32 = alpha * alpha;
+    const ggx_v: f32 = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha2) + alpha2);
+    const ggx_l: f32 = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha2) + alpha2);
+
+    return 0.5 / max(ggx_v + ggx_l, 0.0001);
+}
+
+fn fresnel(v_dot_h: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0, 1.0, 1.0) - f0) * pow(1.0 - v_dot_h, 5.0);
+}
+
+// inverse square falloff, smoothly reaching zero at the range of the light
+fn attenuation(light_distance: f32, range: f32) -> f32 {
+    const falloff: f32 = 1.0 / max(light_distance * light_distance, 0.0001);
+
+    if (range <= 0.0) {
+        return falloff;
+    }
+
+    const window: f32 = clamp(1.0 - pow(light_distance / range, 4.0), 0.0, 1.0);
+
+    return window * window * falloff;
+}
+
+
+// Fraction of light reaching the fragment, filtered over 3x3 texels, in x and whether
+// the fragment is covered by the shadow map in y.
+fn sample_shadow_map(shadow_map: u32, world_position: vec3<f32>) -> vec2<f32> {
+    const clip: vec4<f32> = u_shadows.view_projs[shadow_map] * vec4<f32>(world_position, 1.0);
+    const ndc: vec3<f32> = clip.xyz / clip.w;
+
+    if (ndc.x < -1.0 || ndc.x > 1.0 || ndc.y < -1.0 || ndc.y > 1.0 || ndc.z < 0.0 || ndc.z > 1.0) {
+        return vec2<f32>(1.0, 0.0);
+    }
+
+    const tile_size: f32 = 1.0 / f32(SHADOW_ATLAS_TILES);
+    const tile: vec2<f32> = vec2<f32>(f32(shadow_map % SHADOW_ATLAS_TILES), f32(shadow_map / SHADOW_ATLAS_TILES)) * tile_size;
+    const uv: vec2<f32> = tile + (ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5)) * tile_size;
+
+    // keep the filter from reading neighbouring shadow maps
+    const uv_min: vec2<f32> = tile + vec2<f32>(SHADOW_TEXEL, SHADOW_TEXEL);
+    const uv_max: vec2<f32> = tile + vec2<f32>(tile_size - SHADOW_TEXEL, tile_size - SHADOW_TEXEL);
+
+    var lit: f32 = 0.0;
+
+    var y: i32 = -1;
+    loop {
+        if (y > 1) {
+            break;
+        }
+
+        var x: i32 = -1;
+        loop {
+            if (x > 1) {
+                break;
+            }
+
+            const offset: vec2<f32> = vec2<f32>(f32(x), f32(y)) * SHADOW_TEXEL;
+            lit = lit + textureSampleCompare(r_shadow_atlas, r_shadow_sampler, clamp(uv + offset, uv_min, uv_max), ndc.z);
+
+            continuing {
+                x = x + 1;
+            }
+        }
+
+        continuing {
+            y = y + 1;
+        }
+    }
+
+    return vec2<f32>(lit / 9.0, 1.0);
+}
+
+// Uses the first shadow map of the light covering the fragment, fragments outside of
+// all of them are lit.
+fn shadow(light: u32, world_position: vec3<f32>) -> f32 {
+    var i: u32 = 0u;
+    loop {
+        if (i >= u_lights.lights[light].shadow_map_count) {
+            break;
+        }
+
+        const sample: vec2<f32> = sample_shadow_map(u_lights.lights[light].shadow_map + i, world_position);
+        if (sample.y > 0.0) {
+            return sample.x;
+        }
+
+        continuing {
+            i = i + 1u;
+        }
+    }
+
+    return 1.0;
+}
+
+
+fn select_uv(tex_coord: u32, uv: vec2<f32>, uv1: vec2<f32>) -> vec2<f32> {
+    if (tex_coord == 1u) {
+        return uv1;
+    }
+
+    return uv;
+}
+
+// Applies the normal map with a tangent frame derived from the screen space derivatives
+// of the position and the uvs, the tangents of the file aren't imported.
+fn perturb_normal(n: vec3<f32>, world_position: vec3<f32>, uv: vec2<f32>, tangent_normal: vec3<f32>) -> vec3<f32> {
+    const dp_dx: vec3<f32> = dpdx(world_position);
+    const dp_dy: vec3<f32> = dpdy(world_position);
+    const duv_dx: vec2<f32> = dpdx(uv);
+    const duv_dy: vec2<f32> = dpdy(uv);
+
+    const det: f32 = duv_dx.x * duv_dy.y - duv_dy.x * duv_dx.y;
+
+    if (abs(det) < 0.0000001) {
+        return n;
+    }
+
+    const t_raw: vec3<f32> = (dp_dx * duv_dy.y - dp_dy * duv_dx.y) / det;
+    const t_ortho: vec3<f32> = t_raw - n * dot(n, t_raw);
+
+    if (dot(t_ortho, t_ortho) < 0.0000001) {
+        return n;
+    }
+
+    const t: vec3<f32> = normalize(t_ortho);
+    const b: vec3<f32> = cross(n, t);
+
+    return normalize(t * tangent_normal.x + b * tangent_normal.y + n * tangent_normal.z);
+}
+
+
+[[stage(fragment)]]
+fn fs_main() {
+    const color_uv: vec2<f32> = select_uv(r_primitive.color_tex_coord, in_uv_fs, in_uv1_fs);
+    const base_color: vec4<f32> = textureSample(r_color, r_sampler, color_uv) * r_primitive.color;
+
+    // the cutoff is zero unless the material is alpha masked
+    if (base_color.w < r_primitive.alpha_cutoff) {
+        discard;
+    }
+
+    // roughness is stored in green and metalness in blue
+    const metallic_roughness_uv: vec2<f32> = select_uv(r_primitive.metallic_roughness_tex_coord, in_uv_fs, in_uv1_fs);
+    const metallic_roughness: vec4<f32> = textureSample(r_metallic_roughness, r_metallic_roughness_sampler, metallic_roughness_uv);
+
+    const metallic: f32 = clamp(r_primitive.metallic * metallic_roughness.z, 0.0, 1.0);
+    const roughness: f32 = clamp(r_primitive.roughness * metallic_roughness.y, 0.04, 1.0);
+    const alpha: f32 = roughness * roughness;
+
+    const diffuse_color: vec3<f32> = base_color.xyz * (1.0 - metallic);
+    const f0: vec3<f32> = mix(vec3<f32>(0.04, 0.04, 0.04), base_color.xyz, vec3<f32>(metallic, metallic, metallic));
+
+    // the normal scale is zero without a normal map, which leaves the normal as it is
+    const normal_uv: vec2<f32> = select_uv(r_primitive.normal_tex_coord, in_uv_fs, in_uv1_fs);
+    const normal_sample: vec3<f32> = textureSample(r_normal, r_normal_sampler, normal_uv).xyz * 2.0 - vec3<f32>(1.0, 1.0, 1.0);
+    const tangent_normal: vec3<f32> = vec3<f32>(normal_sample.xy * r_primitive.normal_scale, normal_sample.z);
+
+    const v: vec3<f32> = normalize(u_globals.camera_position.xyz - in_world_position_fs);
+
+    // back faces are only drawn for double sided materials and get the normal of the
+    // front face flipped towards the viewer
+    var geometry_normal: vec3<f32> = normalize(in_normal_fs);
+    if (r_primitive.double_sided != 0u && dot(geometry_normal, v) < 0.0) {
+        geometry_normal = -geometry_normal;
+    }
+
+    const n: vec3<f32> = perturb_normal(geometry_normal, in_world_position_fs, normal_uv, tangent_normal);
+    const n_dot_v: f32 = max(dot(n, v), 0.0001);
+
+    // occlusion is stored in red and only darkens the ambient light
+    const occlusion_uv: vec2<f32> = select_uv(r_primitive.occlusion_tex_coord, in_uv_fs, in_uv1_fs);
+    const occlusion_sample: f32 = textureSample(r_occlusion, r_occlusion_sampler, occlusion_uv).x;
+    const occlusion: f32 = 1.0 + r_primitive.occlusion_strength * (occlusion_sample - 1.0);
+
+    var color: vec3<f32> = u_lights.ambient.xyz * base_color.xyz * occlusion;
+
+    var i: u32 = 0u;
+    loop {
+        if (i >= u_lights.count) {
+            break;
+        }
+
+        var l: vec3<f32> = -u_lights.lights[i].direction.xyz;
+        var radiance: vec3<f32> = u_lights.lights[i].color.xyz;
+
+        if (u_lights.lights[i].kind != LIGHT_KIND_DIRECTIONAL) {
+            const to_light: vec3<f32> = u_lights.lights[i].position.xyz - in_world_position_fs;
+            const light_distance: f32 = length(to_light);
+
+            l = to_light / max(light_distance, 0.0001);
+            radiance = radiance * attenuation(light_distance, u_lights.lights[i].range);
+
+            if (u_lights.lights[i].kind == LIGHT_KIND_SPOT) {
+                const cone_cos: f32 = dot(u_lights.lights[i].direction.xyz, -l);
+
+                radiance = radiance * smoothStep(
+                    u_lights.lights[i].outer_cone_cos,
+                    u_lights.lights[i].inner_cone_cos,
+                    cone_cos
+                );
+            }
+        }
+
+        if (r_primitive.receive_shadows != 0u) {
+            radiance = radiance * shadow(i, in_world_position_fs);
+        }
+
+        const h: vec3<f32> = normalize(l + v);
+        const n_dot_l: f32 = max(dot(n, l), 0.0);
+        const n_dot_h: f32 = max(dot(n, h), 0.0);
+        const v_dot_h: f32 = max(dot(v, h), 0.0);
+
+        const f: vec3<f32> = fresnel(v_dot_h, f0);
+        const specular: vec3<f32> = f * (distribution(n_dot_h, alpha) * visibility(n_dot_v, n_dot_l, alpha));
+        const diffuse: vec3<f32> = (vec3<f32>(1.0, 1.0, 1.0) - f) * diffuse_color * (1.0 / PI);
+
+        color = color + (diffuse + specular) * radiance * n_dot_l;
+
+        continuing {
+            i = i + 1u;
+        }
+    }
+
+    const emissive_uv: vec2<f32> = select_uv(r_primitive.emissive_tex_coord, in_uv_fs, in_uv1_fs);
+    color = color + r_primitive.emissive.xyz * textureSample(r_emissive, r_emissive_sampler, emissive_uv).xyz;
+
+    out_color = vec4<f32>(color, base_color.w);
+}
@@ -0,0 +1,54 @@
+[[location(0)]]
+var<in> in_position: vec3<f32>;
+
+[[location(4)]]
+var<in> in_joints_vs: vec4<u32>;
+
+[[location(5)]]
+var<in> in_weights_vs: vec4<f32>;
+
+[[builtin(position)]]
+var<out> out_position: vec4<f32>;
+
+
+[[block]]
+struct ShadowView {
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> u_shadow_view: ShadowView;
+
+
+[[block]]
+struct Locals {
+    model: mat4x4<f32>;
+};
+
+[[group(1), binding(0)]]
+var r_locals: Locals;
+
+
+[[block]]
+struct Joints {
+    matrices: [[stride(64)]] array<mat4x4<f32>, 64u>;
+};
+
+[[group(1), binding(1)]]
+var r_joints: Joints;
+
+
+[[stage(vertex)]]
+fn vs_main() {
+    var model: mat4x4<f32> = r_locals.model;
+    if (in_weights_vs.x + in_weights_vs.y + in_weights_vs.z + in_weights_vs.w > 0.0) {
+        model = model * (
+            r_joints.matrices[in_joints_vs.x] * in_weights_vs.x +
+            r_joints.matrices[in_joints_vs.y] * in_weights_vs.y +
+            r_joints.matrices[in_joints_vs.z] * in_weights_vs.z +
+            r_joints.matrices[in_joints_vs.w] * in_weights_vs.w
+        );
+    }
+
+    out_position = u_shadow_view.view_proj * model * vec4<f32>(in_position, 1.0);
+}
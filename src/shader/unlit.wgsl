@@ -7,12 +7,24 @@ var<in> in_uv_vs: vec2<f32>;
 [[location(2)]]
 var<in> in_normal_vs: vec3<f32>;
 
+[[location(3)]]
+var<in> in_uv1_vs: vec2<f32>;
+
+[[location(4)]]
+var<in> in_joints_vs: vec4<u32>;
+
+[[location(5)]]
+var<in> in_weights_vs: vec4<f32>;
+
 [[location(0)]]
 var<out> out_uv: vec2<f32>;
 
 [[location(1)]]
 var<out> out_normal: vec3<f32>;
 
+[[location(2)]]
+var<out> out_uv1: vec2<f32>;
+
 [[builtin(position)]]
 var<out> out_position: vec4<f32>;
 
@@ -20,6 +32,7 @@ var<out> out_position: vec4<f32>;
 [[block]]
 struct Globals {
     view_proj: mat4x4<f32>;
+    camera_position: vec4<f32>;
 };
 
 [[group(0), binding(0)]]
@@ -35,11 +48,32 @@ struct Locals {
 var r_locals: Locals;
 
 
+[[block]]
+struct Joints {
+    matrices: [[stride(64)]] array<mat4x4<f32>, 64u>;
+};
+
+[[group(1), binding(1)]]
+var r_joints: Joints;
+
+
 [[stage(vertex)]]
 fn vs_main() {
     out_uv = in_uv_vs;
     out_normal = in_normal_vs;
-    out_position = u_globals.view_proj * r_locals.model * vec4<f32>(in_position, 1.0);
+    out_uv1 = in_uv1_vs;
+
+    var model: mat4x4<f32> = r_locals.model;
+    if (in_weights_vs.x + in_weights_vs.y + in_weights_vs.z + in_weights_vs.w > 0.0) {
+        model = model * (
+            r_joints.matrices[in_joints_vs.x] * in_weights_vs.x +
+            r_joints.matrices[in_joints_vs.y] * in_weights_vs.y +
+            r_joints.matrices[in_joints_vs.z] * in_weights_vs.z +
+            r_joints.matrices[in_joints_vs.w] * in_weights_vs.w
+        );
+    }
+
+    out_position = u_globals.view_proj * model * vec4<f32>(in_position, 1.0);
 }
 
 
@@ -49,6 +83,9 @@ var<in> in_uv_fs: vec2<f32>;
 [[location(1)]]
 var<in> in_normal_fs: vec3<f32>;
 
+[[location(2)]]
+var<in> in_uv1_fs: vec2<f32>;
+
 [[location(0)]]
 var<out> out_color: vec4<f32>;
 
@@ -56,6 +93,18 @@ var<out> out_color: vec4<f32>;
 [[block]]
 struct Primitive {
     color: vec4<f32>;
+    emissive: vec4<f32>;
+    color_tex_coord: u32;
+    metallic: f32;
+    roughness: f32;
+    receive_shadows: u32;
+    metallic_roughness_tex_coord: u32;
+    normal_tex_coord: u32;
+    occlusion_tex_coord: u32;
+    emissive_tex_coord: u32;
+    normal_scale: f32;
+    occlusion_strength: f32;
+    alpha_cutoff: f32;
 };
 
 [[group(2), binding(0)]]
@@ -69,5 +118,17 @@ var r_sampler: sampler;
 
 [[stage(fragment)]]
 fn fs_main() {
-    out_color = textureSample(r_color, r_sampler, in_uv_fs) * r_primitive.color;
+    var color_uv: vec2<f32> = in_uv_fs;
+    if (r_primitive.color_tex_coord == 1u) {
+        color_uv = in_uv1_fs;
+    }
+
+    const color: vec4<f32> = textureSample(r_color, r_sampler, color_uv) * r_primitive.color;
+
+    // the cutoff is zero unless the material is alpha masked
+    if (color.w < r_primitive.alpha_cutoff) {
+        discard;
+    }
+
+    out_color = color;
 }
\ No newline at end of file
@@ -1,3 +1,3 @@
 mod quad;
 
-pub use quad::_generate_quad;
+pub use quad::generate_quad;
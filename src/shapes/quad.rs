@@ -1,27 +1,39 @@
 use crate::gpu::{Geometry, Vertex};
 use wgpu::Device;
 
-pub fn _generate_quad(device: &Device) -> Geometry {
+pub fn generate_quad(device: &Device) -> Geometry {
     let vertex_data = [
         Vertex {
             position: [-1.0, 1.0, 0.0],
             uv: [-1.0, 1.0],
             normal: [0.0, 0.0, 1.0],
+            uv1: [-1.0, 1.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
         },
         Vertex {
             position: [1.0, 1.0, 0.0],
             uv: [1.0, 1.0],
             normal: [0.0, 0.0, 1.0],
+            uv1: [1.0, 1.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
         },
         Vertex {
             position: [1.0, -1.0, 0.0],
             uv: [1.0, -1.0],
             normal: [0.0, 0.0, 1.0],
+            uv1: [1.0, -1.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
         },
         Vertex {
             position: [-1.0, -1.0, 0.0],
             uv: [-1.0, -1.0],
             normal: [0.0, 0.0, 1.0],
+            uv1: [-1.0, -1.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
         },
     ];
 
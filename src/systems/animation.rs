@@ -0,0 +1,267 @@
+use crate::{
+    components::{AnimationComponent, AnimationState, Pose},
+    resources::{
+        animation::{advance_time, AnimationClip, ChannelValue},
+        scene::Scene,
+    },
+};
+
+impl Pose {
+    fn blend(self, other: Pose, s: f32) -> Pose {
+        let weights = if self.weights.len() == other.weights.len() {
+            self.weights
+                .iter()
+                .zip(&other.weights)
+                .map(|(a, b)| a + (b - a) * s)
+                .collect()
+        } else {
+            other.weights
+        };
+
+        let other_rotation = if self.rotation.dot(other.rotation) < 0.0 {
+            -other.rotation
+        } else {
+            other.rotation
+        };
+
+        Pose {
+            translation: self.translation.lerp(other.translation, s),
+            rotation: self.rotation.slerp(other_rotation, s).normalize(),
+            scale: self.scale.lerp(other.scale, s),
+            weights,
+        }
+    }
+}
+
+fn sample_pose(clip: &AnimationClip, time: f32, poses: &mut [Pose]) {
+    for channel in &clip.channels {
+        let pose = &mut poses[channel.target];
+
+        match channel.sample(time, &mut pose.weights) {
+            ChannelValue::Translation(translation) => pose.translation = translation,
+            ChannelValue::Rotation(rotation) => pose.rotation = rotation,
+            ChannelValue::Scale(scale) => pose.scale = scale,
+            ChannelValue::Weights => {}
+        }
+    }
+}
+
+// Advances the state and records the events it passed.
+fn advance(
+    animation: &mut AnimationComponent,
+    state: AnimationState,
+    delta_time: f32,
+) -> AnimationState {
+    let clip = &animation.clips[state.clip];
+
+    let (time, passed) = advance_time(
+        state.time,
+        delta_time * animation.speed,
+        clip.duration,
+        animation.looping,
+    );
+
+    for event in &animation.events {
+        let passed = passed.iter().any(|range| range.contains(event.time))
+            || (!state.started && event.time == state.time);
+
+        if event.clip == state.clip && passed {
+            animation.fired_events.push(event.name.clone());
+        }
+    }
+
+    AnimationState {
+        clip: state.clip,
+        time,
+        started: true,
+    }
+}
+
+pub struct AnimationSystem;
+
+impl AnimationSystem {
+    // Has to run before `TransformSystem::update` to be visible in the same frame.
+    pub fn update(scene: &mut Scene, delta_time: f32) {
+        let transforms = &mut scene.transforms;
+        let meshes = &mut scene.meshes;
+        let delta_time = delta_time / 1000.0;
+
+        for (_, animation) in scene.animations.iter_mut() {
+            animation.fired_events.clear();
+
+            let current = match animation.current {
+                Some(current) => advance(animation, current, delta_time),
+                None => continue,
+            };
+
+            animation.current = Some(current);
+
+            let mut poses = animation.bind_pose.clone();
+            sample_pose(&animation.clips[current.clip], current.time, &mut poses);
+
+            if let Some(previous) = animation.previous {
+                let previous = advance(animation, previous, delta_time);
+
+                animation.blend_time += delta_time;
+
+                if animation.blend_time < animation.blend_duration {
+                    let mut previous_poses = animation.bind_pose.clone();
+                    sample_pose(
+                        &animation.clips[previous.clip],
+                        previous.time,
+                        &mut previous_poses,
+                    );
+
+                    let s = animation.blend_time / animation.blend_duration;
+
+                    poses = previous_poses
+                        .into_iter()
+                        .zip(poses)
+                        .map(|(previous_pose, pose)| previous_pose.blend(pose, s))
+                        .collect();
+
+                    animation.previous = Some(previous);
+                } else {
+                    animation.previous = None;
+                }
+            }
+
+            for (&target, pose) in animation.targets.iter().zip(poses) {
+                if let Some(transform) = transforms.get_mut(target) {
+                    transform.translation = pose.translation;
+                    transform.rotation = pose.rotation;
+                    transform.scale = pose.scale;
+                }
+
+                if let Some(mesh) = meshes.get_mut(target) {
+                    mesh.weights = pose.weights;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::TransformComponent,
+        resources::animation::{Channel, Interpolation, Keyframes},
+    };
+    use slotmap::DefaultKey;
+    use std::rc::Rc;
+
+    fn clip(name: &str, keyframes: Keyframes) -> Rc<AnimationClip> {
+        Rc::new(AnimationClip {
+            name: Some(String::from(name)),
+            duration: 1.0,
+            channels: vec![Channel {
+                target: 0,
+                interpolation: Interpolation::Step,
+                times: vec![0.0],
+                keyframes,
+            }],
+        })
+    }
+
+    fn animated_scene() -> (Scene, DefaultKey) {
+        let mut scene = Scene::new();
+        let entity = scene.create_entity(TransformComponent {
+            translation: glam::vec3(1.0, 2.0, 3.0),
+            ..Default::default()
+        });
+
+        let animation = AnimationComponent {
+            clips: vec![
+                clip("move", Keyframes::Translation(vec![glam::Vec3::zero()])),
+                clip("grow", Keyframes::Scale(vec![glam::Vec3::splat(2.0)])),
+            ],
+            targets: vec![entity],
+            bind_pose: vec![Pose::new(&scene.transforms[entity], None)],
+            ..Default::default()
+        };
+
+        scene.animations.insert(entity, animation);
+
+        (scene, entity)
+    }
+
+    #[test]
+    fn properties_a_clip_does_not_animate_return_to_the_bind_pose() {
+        let (mut scene, entity) = animated_scene();
+
+        scene.animations[entity].play(0);
+        AnimationSystem::update(&mut scene, 100.0);
+
+        assert_eq!(scene.transforms[entity].translation, glam::Vec3::zero());
+
+        scene.animations[entity].play(1);
+        AnimationSystem::update(&mut scene, 100.0);
+
+        assert_eq!(
+            scene.transforms[entity].translation,
+            glam::vec3(1.0, 2.0, 3.0)
+        );
+        assert_eq!(scene.transforms[entity].scale, glam::Vec3::splat(2.0));
+    }
+
+    #[test]
+    fn blending_fades_from_the_previous_clip() {
+        let (mut scene, entity) = animated_scene();
+
+        scene.animations[entity].play(0);
+        AnimationSystem::update(&mut scene, 100.0);
+
+        scene.animations[entity].blend_to(1, 1.0);
+        AnimationSystem::update(&mut scene, 500.0);
+
+        assert_eq!(
+            scene.transforms[entity].translation,
+            glam::vec3(0.5, 1.0, 1.5)
+        );
+        assert_eq!(scene.transforms[entity].scale, glam::Vec3::splat(1.5));
+
+        AnimationSystem::update(&mut scene, 600.0);
+
+        assert!(scene.animations[entity].previous.is_none());
+        assert_eq!(
+            scene.transforms[entity].translation,
+            glam::vec3(1.0, 2.0, 3.0)
+        );
+        assert_eq!(scene.transforms[entity].scale, glam::Vec3::splat(2.0));
+    }
+
+    #[test]
+    fn clips_are_found_by_name_and_stop_playing() {
+        let (mut scene, entity) = animated_scene();
+
+        let animation = &mut scene.animations[entity];
+        assert_eq!(animation.find_clip("grow"), Some(1));
+        assert_eq!(animation.find_clip("jump"), None);
+
+        animation.play(1);
+        animation.stop();
+        AnimationSystem::update(&mut scene, 100.0);
+
+        assert!(scene.animations[entity].current.is_none());
+        assert_eq!(scene.transforms[entity].scale, glam::Vec3::one());
+    }
+
+    #[test]
+    fn events_at_the_start_fire_on_the_first_update() {
+        let (mut scene, entity) = animated_scene();
+
+        let animation = &mut scene.animations[entity];
+        animation.looping = false;
+        animation.add_keyframe_event(0, 0, 0, "start");
+        animation.play(0);
+
+        AnimationSystem::update(&mut scene, 100.0);
+
+        assert_eq!(scene.animations[entity].fired_events, vec!["start"]);
+
+        AnimationSystem::update(&mut scene, 100.0);
+
+        assert!(scene.animations[entity].fired_events.is_empty());
+    }
+}
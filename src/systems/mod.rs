@@ -1,5 +1,9 @@
+mod animation;
+mod morph;
 mod player;
 mod transform;
 
+pub use animation::AnimationSystem;
+pub use morph::MorphSystem;
 pub use player::PlayerSystem;
 pub use transform::TransformSystem;
@@ -0,0 +1,22 @@
+use crate::{
+    gpu::Context,
+    resources::{manager::ResourceManager, scene::Scene},
+};
+
+pub struct MorphSystem;
+
+impl MorphSystem {
+    // Runs after `AnimationSystem::update`. Only primitives whose geometry is blended with
+    // other weights than the ones of their mesh are blended again.
+    pub fn update(context: &Context, resource_manager: &mut ResourceManager, scene: &mut Scene) {
+        for (_, mesh) in scene.meshes.iter_mut() {
+            if mesh.weights.is_empty() {
+                continue;
+            }
+
+            for primitive in mesh.primitives.iter_mut() {
+                resource_manager.set_morph_weights(context, primitive, &mesh.weights);
+            }
+        }
+    }
+}